@@ -0,0 +1,27 @@
+use std::{hint::black_box, time::Instant};
+
+const ITERATIONS: usize = 20;
+
+fn run(name: &str, data: &[u8]) {
+    let expected = ripgzip::decompress_to_vec(data).unwrap();
+    let mut output = vec![];
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        output.clear();
+        ripgzip::decompress(black_box(data), &mut output).unwrap();
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    assert_eq!(output, expected);
+
+    println!("dynamic_blocks: {}, {:?} per decode", name, elapsed);
+}
+
+fn main() {
+    // Both are mostly dynamic blocks, so building the trees matters.
+    run("app", include_bytes!("../data/ok/05-app.gz"));
+    run(
+        "war and peace",
+        include_bytes!("../data/ok/06-war-and-peace.txt.gz"),
+    );
+}
@@ -0,0 +1,39 @@
+use std::{hint::black_box, time::Instant};
+
+use ripgzip::GzDecoder;
+
+const ITERATIONS: usize = 20;
+
+/// Decode `members` empty members with one `GzDecoder`, which resets its
+/// history window, byte count and CRC digest for each of them.
+fn run(members: usize) {
+    // Header, an empty final stored block and the footer of no data.
+    let member = [
+        &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255][..],
+        &[0x01, 0x00, 0x00, 0xff, 0xff],
+        &[0; 8],
+    ]
+    .concat();
+    let data = member.repeat(members);
+    let mut buf = [0u8; 16];
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut decoder = GzDecoder::new(black_box(data.as_slice()));
+        while decoder.decode_into_buffer(&mut buf).unwrap() > 0 {}
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+
+    println!(
+        "member_reset: {} members, {:?} per decode, {:?} per member",
+        members,
+        elapsed,
+        elapsed / members as u32
+    );
+}
+
+fn main() {
+    // The time per member should stay flat as the member count grows.
+    for members in [100, 1_000, 10_000] {
+        run(members);
+    }
+}
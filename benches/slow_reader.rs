@@ -0,0 +1,57 @@
+use std::{
+    hint::black_box,
+    io::{self, BufReader, Read},
+    time::Instant,
+};
+
+const ITERATIONS: usize = 20;
+const CHUNK_SIZE: usize = 64;
+
+/// Hands out at most `CHUNK_SIZE` bytes per call, like a socket with a small
+/// receive buffer.
+struct SlowReader<'a> {
+    data: &'a [u8],
+    reads: usize,
+}
+
+impl Read for SlowReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+        let size = buf.len().min(CHUNK_SIZE);
+        self.data.read(&mut buf[..size])
+    }
+}
+
+fn run(name: &str, data: &[u8], decode: impl Fn(&mut SlowReader, &mut Vec<u8>)) {
+    let mut output = vec![];
+    let mut reads = 0;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        output.clear();
+        let mut reader = SlowReader {
+            data: black_box(data),
+            reads: 0,
+        };
+        decode(&mut reader, &mut output);
+        reads += reader.reads;
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+
+    println!(
+        "slow_reader: {}, {:?} per decode, {} reads per decode",
+        name,
+        elapsed,
+        reads / ITERATIONS
+    );
+}
+
+fn main() {
+    let data = include_bytes!("../data/ok/06-war-and-peace.txt.gz");
+
+    run("64-byte BufReader", data, |reader, output| {
+        ripgzip::decompress(BufReader::with_capacity(CHUNK_SIZE, reader), output).unwrap()
+    });
+    run("decompress_buffered 64 KiB", data, |reader, output| {
+        ripgzip::decompress_buffered(reader, output, 64 << 10).unwrap()
+    });
+}
@@ -0,0 +1,37 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::{hint::black_box, sync::atomic::Ordering, time::Instant};
+
+use common::{CountingAllocator, ALLOCATIONS};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: usize = 20;
+
+fn run(name: &str, data: &[u8]) {
+    let mut output = vec![];
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        output.clear();
+        ripgzip::decompress(black_box(data), &mut output).unwrap();
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - before) / ITERATIONS;
+
+    println!(
+        "write_previous: {}, {:?} per decode, {} allocations per decode",
+        name, elapsed, allocations
+    );
+}
+
+fn main() {
+    run(
+        "war and peace",
+        include_bytes!("../data/ok/06-war-and-peace.txt.gz"),
+    );
+    run("app", include_bytes!("../data/ok/05-app.gz"));
+}
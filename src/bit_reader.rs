@@ -14,8 +14,9 @@ pub struct BitSequence {
 
 impl BitSequence {
     pub fn new(bits: u16, len: u8) -> Self {
+        let mask = u16::MAX.checked_shr(16 - len as u32).unwrap_or(0);
         Self {
-            bits: bits & ((1u16 << len) - 1),
+            bits: bits & mask,
             len,
         }
     }
@@ -28,14 +29,33 @@ impl BitSequence {
         self.len
     }
 
-    pub fn concat(self, other: Self) -> Self {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn fits(&self) -> bool {
+        self.len >= 16 || self.bits >> self.len == 0
+    }
+
+    /// Append `other` above the bits of `self`, or `None` if the result
+    /// wouldn't fit into 16 bits.
+    pub fn try_concat(self, other: Self) -> Option<Self> {
+        debug_assert!(self.fits(), "stray high bits in {:?}", self);
+        debug_assert!(other.fits(), "stray high bits in {:?}", other);
         if self.len + other.len > 16 {
-            panic!("Too big sequences to concat");
+            return None;
         }
         let left_part = other.bits << self.len;
-        Self {
-            bits: left_part + self.bits,
+        Some(Self {
+            bits: left_part | self.bits,
             len: self.len + other.len,
+        })
+    }
+
+    pub fn concat(self, other: Self) -> Self {
+        match self.try_concat(other) {
+            Some(seq) => seq,
+            None => panic!("Too big sequences to concat"),
         }
     }
 }
@@ -43,8 +63,9 @@ impl BitSequence {
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct BitReader<T> {
-    pub stream: T,
+    stream: T,
     buffer: BitSequence,
+    bytes_read: u64,
 }
 
 impl<T: BufRead> BitReader<T> {
@@ -52,11 +73,17 @@ impl<T: BufRead> BitReader<T> {
         Self {
             stream,
             buffer: BitSequence { bits: 0, len: 0 },
+            bytes_read: 0,
         }
     }
 
+    /// Read `len` bits least-significant-first. This is the bit order DEFLATE
+    /// uses for everything except Huffman codes. Panics if `len` exceeds 16.
     pub fn read_bits(&mut self, mut len: u8) -> io::Result<BitSequence> {
+        assert!(len <= 16, "can't read {} bits at once", len);
         let mut result = BitSequence::new(0, 0);
+        // `result` only ever grows by bits that are still owed, so it holds
+        // at most the `len <= 16` bits asked for and `concat` can't overflow.
         while len > 0 {
             if len <= self.buffer.len() {
                 let bits = self.buffer.bits() & ((1u16 << len) - 1);
@@ -66,6 +93,7 @@ impl<T: BufRead> BitReader<T> {
                 return Ok(result);
             }
             let byte = self.stream.read_u8()?;
+            self.bytes_read += 1;
             result = result.concat(self.buffer);
             len -= self.buffer.len();
             self.buffer = BitSequence::new(byte as u16, 8);
@@ -73,10 +101,86 @@ impl<T: BufRead> BitReader<T> {
         Ok(result)
     }
 
+    /// Read `len` bits most-significant-first, for bitstreams other than
+    /// DEFLATE that pack bits from the top of each byte. Don't mix it with
+    /// `read_bits` inside one byte: the two orders consume opposite ends.
+    /// Panics if `len` exceeds 16.
+    pub fn read_bits_msb(&mut self, mut len: u8) -> io::Result<BitSequence> {
+        assert!(len <= 16, "can't read {} bits at once", len);
+        let total = len;
+        let mut result = 0u16;
+        while len > 0 {
+            if self.buffer.is_empty() {
+                let byte = self.stream.read_u8()?;
+                self.bytes_read += 1;
+                self.buffer = BitSequence::new(byte as u16, 8);
+            }
+            let take = len.min(self.buffer.len());
+            let rest = self.buffer.len() - take;
+            result = (result << take) | (self.buffer.bits() >> rest);
+            self.buffer = BitSequence::new(self.buffer.bits(), rest);
+            len -= take;
+        }
+        Ok(BitSequence::new(result, total))
+    }
+
+    /// Look at up to `len` (at most 15) upcoming bits without consuming them.
+    /// Fewer bits come back near the end of the stream or of its buffer.
+    pub fn peek_bits(&mut self, len: u8) -> io::Result<BitSequence> {
+        assert!(len < 16, "can't peek {} bits", len);
+        let mut bits = self.buffer.bits() as u32;
+        let mut count = self.buffer.len();
+        for &byte in self.stream.fill_buf()?.iter().take(2) {
+            if count >= len {
+                break;
+            }
+            bits |= (byte as u32) << count;
+            count += 8;
+        }
+        Ok(BitSequence::new(bits as u16, count.min(len)))
+    }
+
+    /// The underlying stream, or an error if bits of a partially read byte
+    /// are still buffered and reading the stream would skip them. Use
+    /// `borrow_reader_from_boundary` to drop those bits deliberately.
+    pub fn stream_mut(&mut self) -> io::Result<&mut T> {
+        if !self.buffer.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "bits of a partially read byte are still buffered",
+            ));
+        }
+        Ok(&mut self.stream)
+    }
+
+    /// Give up the reader. Buffered bits of a partially read byte are lost.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    /// Drop the buffered bits, aligning to the next byte boundary, and borrow
+    /// the underlying stream.
     pub fn borrow_reader_from_boundary(&mut self) -> &mut T {
         self.buffer = BitSequence::new(0, 0);
         &mut self.stream
     }
+
+    /// Drop the buffered bits and fill `buf` with whole bytes from the stream.
+    pub fn read_aligned(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.borrow_reader_from_boundary().read_exact(buf)?;
+        self.bytes_read += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Number of bytes pulled from the underlying stream so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Number of bits consumed so far, not counting the ones still buffered.
+    pub fn bits_read(&self) -> u64 {
+        self.bytes_read * 8 - self.buffer.len() as u64
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -86,6 +190,34 @@ mod tests {
     use super::*;
     use byteorder::ReadBytesExt;
 
+    #[test]
+    fn new_masks_high_bits() {
+        let seq = BitSequence::new(0b1111_0101, 4);
+        assert_eq!(seq.bits(), 0b0101);
+        assert_eq!(seq.len(), 4);
+        assert_eq!(
+            seq.concat(BitSequence::new(0xff, 2)),
+            BitSequence::new(0b11_0101, 6)
+        );
+        assert_eq!(
+            seq.try_concat(BitSequence::new(0, 13)),
+            None,
+            "17 bits don't fit"
+        );
+    }
+
+    #[test]
+    fn new_full_width() {
+        assert_eq!(BitSequence::new(0xffff, 16).bits(), 0xffff);
+        assert_eq!(BitSequence::new(0xffff, 0).bits(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too big sequences to concat")]
+    fn concat_overflow() {
+        BitSequence::new(0, 9).concat(BitSequence::new(0, 8));
+    }
+
     #[test]
     fn read_bits() -> io::Result<()> {
         let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111];
@@ -103,6 +235,61 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn read_bits_exactly_buffered() -> io::Result<()> {
+        let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111, 0b00001111, 0b11110000];
+        let mut reader = BitReader::new(data);
+        // Refill, then take the whole byte.
+        assert_eq!(reader.read_bits(8)?, BitSequence::new(0b01100011, 8));
+        assert_eq!(reader.bytes_read(), 1);
+        // Take all of what remains buffered without a refill.
+        assert_eq!(reader.read_bits(3)?, BitSequence::new(0b011, 3));
+        assert_eq!(reader.read_bits(5)?, BitSequence::new(0b11011, 5));
+        assert_eq!(reader.bytes_read(), 2);
+        // The rest after the buffered bits is exactly the refilled byte.
+        assert_eq!(reader.read_bits(2)?, BitSequence::new(0b11, 2));
+        assert_eq!(
+            reader.read_bits(14)?,
+            BitSequence::new(0b00001111101011, 14)
+        );
+        assert_eq!(reader.bytes_read(), 4);
+        // Two whole refills in a single call.
+        let mut reader = BitReader::new(&data[3..]);
+        assert_eq!(reader.read_bits(16)?, BitSequence::new(0xf00f, 16));
+        assert_eq!(reader.bytes_read(), 2);
+        assert_eq!(
+            reader.read_bits(1).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn read_16_bits_at_any_offset() -> io::Result<()> {
+        let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111, 0b00001111];
+        let all = u32::from_le_bytes(data.try_into().unwrap());
+        for offset in 0..=8 {
+            let mut reader = BitReader::new(data);
+            reader.read_bits(offset)?;
+            let expected = (all >> offset) as u16;
+            assert_eq!(
+                reader.read_bits(16)?,
+                BitSequence::new(expected, 16),
+                "offset {}",
+                offset
+            );
+            assert_eq!(reader.bits_read(), offset as u64 + 16);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "can't read 17 bits at once")]
+    fn read_bits_too_many() {
+        let data: &[u8] = &[0; 4];
+        let _ = BitReader::new(data).read_bits(17);
+    }
+
     #[test]
     fn borrow_reader_from_boundary() -> io::Result<()> {
         let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111];
@@ -112,4 +299,81 @@ mod tests {
         assert_eq!(reader.read_bits(8)?, BitSequence::new(0b10101111, 8));
         Ok(())
     }
+
+    #[test]
+    fn stream_mut() -> io::Result<()> {
+        let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111];
+        let mut reader = BitReader::new(data);
+        assert_eq!(reader.stream_mut()?.len(), 3);
+        reader.read_bits(3)?;
+        assert_eq!(
+            reader.stream_mut().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        reader.read_bits(5)?;
+        assert_eq!(reader.stream_mut()?.read_u8()?, 0b11011011);
+        reader.read_bits(1)?;
+        assert!(reader.stream_mut().is_err());
+        assert!(reader.borrow_reader_from_boundary().is_empty());
+        assert!(reader.stream_mut()?.is_empty());
+        assert!(reader.into_inner().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_read() -> io::Result<()> {
+        let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111, 0, 0];
+        let mut reader = BitReader::new(data);
+        assert_eq!(reader.bytes_read(), 0);
+        reader.read_bits(9)?;
+        assert_eq!(reader.bytes_read(), 2);
+        let mut buf = [0u8; 2];
+        reader.read_aligned(&mut buf)?;
+        assert_eq!(buf, [0b10101111, 0]);
+        assert_eq!(reader.bytes_read(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn peek_bits() -> io::Result<()> {
+        let data: &[u8] = &[0b01100011, 0b11011011];
+        let mut reader = BitReader::new(data);
+        assert_eq!(reader.peek_bits(3)?, BitSequence::new(0b011, 3));
+        assert_eq!(reader.bytes_read(), 0);
+        assert_eq!(reader.read_bits(5)?, BitSequence::new(0b00011, 5));
+        assert_eq!(reader.peek_bits(10)?, BitSequence::new(0b1011011011, 10));
+        assert_eq!(reader.peek_bits(15)?, BitSequence::new(0b11011011011, 11));
+        assert_eq!(reader.read_bits(11)?, BitSequence::new(0b11011011011, 11));
+        assert_eq!(reader.peek_bits(15)?, BitSequence::new(0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn read_bits_msb() -> io::Result<()> {
+        let data: &[u8] = &[0b01100011, 0b11011011, 0b10101111];
+        let mut reader = BitReader::new(data);
+        assert_eq!(reader.read_bits_msb(3)?, BitSequence::new(0b011, 3));
+        assert_eq!(reader.read_bits_msb(4)?, BitSequence::new(0b0001, 4));
+        assert_eq!(reader.read_bits_msb(3)?, BitSequence::new(0b111, 3));
+        assert_eq!(
+            reader.read_bits_msb(10)?,
+            BitSequence::new(0b0110111010, 10)
+        );
+        assert_eq!(
+            reader.read_bits_msb(5).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut reader = BitReader::new(data);
+        assert_eq!(reader.read_bits(3)?, BitSequence::new(0b011, 3));
+        assert_eq!(reader.read_bits(4)?, BitSequence::new(0b1100, 4));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "can't read 17 bits at once")]
+    fn read_bits_msb_too_many() {
+        let data: &[u8] = &[0; 4];
+        let _ = BitReader::new(data).read_bits_msb(17);
+    }
 }
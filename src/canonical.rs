@@ -0,0 +1,119 @@
+#![forbid(unsafe_code)]
+
+use std::io::{self, BufRead, Read, Write};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    bit_reader::BitReader,
+    deflate::DeflateReader,
+    error::DecodeError,
+    gzip::{CompressionMethod, GzipReader, MemberHeader, MemberReader},
+    inflate_blocks,
+    options::{CanonicalizeOptions, DecodeOptions},
+    tracking_writer::TrackingWriter,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const OS_UNKNOWN: u8 = 255;
+
+/// Passes reads through while keeping a copy of every consumed byte.
+struct RecordingReader<T> {
+    inner: T,
+    recorded: Vec<u8>,
+}
+
+impl<T: BufRead> Read for RecordingReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let size = available.len().min(buf.len());
+        buf[..size].copy_from_slice(&available[..size]);
+        self.consume(size);
+        Ok(size)
+    }
+}
+
+impl<T: BufRead> BufRead for RecordingReader<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.recorded.extend_from_slice(&buf[..amt]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+fn canonical_header(original: &MemberHeader, options: &CanonicalizeOptions) -> MemberHeader {
+    MemberHeader {
+        compression_method: CompressionMethod::Deflate,
+        modification_time: 0,
+        extra: None,
+        name: None,
+        comment: None,
+        extra_flags: match options.normalize_extra_flags {
+            true => 0,
+            false => original.extra_flags,
+        },
+        os: match options.normalize_os {
+            true => OS_UNKNOWN,
+            false => original.os,
+        },
+        has_crc: true,
+        is_text: false,
+    }
+}
+
+/// Rewrite every member of `input` with a canonical header (no name, comment
+/// or extra field, zero MTIME, with FHCRC) while copying the compressed body
+/// and footer verbatim. The OS and XFL bytes are kept. Bodies are decoded
+/// only to validate them against their footers.
+pub fn canonicalize<R: BufRead, W: Write>(input: R, output: W) -> Result<(), DecodeError> {
+    canonicalize_with_options(input, output, &CanonicalizeOptions::default())
+}
+
+/// Same as `canonicalize`, optionally normalizing the OS and XFL bytes too.
+pub fn canonicalize_with_options<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    canonicalize_options: &CanonicalizeOptions,
+) -> Result<(), DecodeError> {
+    Ok(canonicalize_members(input, output, canonicalize_options)?)
+}
+
+fn canonicalize_members<R: BufRead, W: Write>(
+    input: R,
+    mut output: W,
+    canonicalize_options: &CanonicalizeOptions,
+) -> Result<()> {
+    let options = DecodeOptions::default();
+    let mut gzip_reader = GzipReader::new(RecordingReader {
+        inner: input,
+        recorded: vec![],
+    });
+
+    while let Some(member) = gzip_reader.read_header() {
+        let (header, _flags) = member?;
+        if let CompressionMethod::Unknown(method) = header.compression_method {
+            bail!(DecodeError::UnsupportedMethod(method))
+        }
+        gzip_reader.check_body_present()?;
+        gzip_reader.reader().recorded.clear();
+
+        let mut writer = TrackingWriter::new(io::sink());
+        let mut deflate_reader = DeflateReader::new(BitReader::new(gzip_reader.reader()));
+        inflate_blocks(&mut deflate_reader, &mut writer, &options, 0, 0)?;
+        let body_size = deflate_reader.bytes_read() as usize;
+
+        let (footer, _reader) = MemberReader::new(gzip_reader.reader()).read_footer()?;
+        footer.verify(writer.byte_count(), writer.crc32())?;
+
+        canonical_header(&header, canonicalize_options).write_to(&mut output)?;
+        output.write_all(&gzip_reader.reader().recorded[..body_size])?;
+        footer.write_to(&mut output)?;
+    }
+    Ok(())
+}
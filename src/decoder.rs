@@ -0,0 +1,388 @@
+#![forbid(unsafe_code)]
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Read, Write},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    bit_reader::BitReader,
+    check_block_count, check_checksum_policy, check_footer, check_output_size,
+    deflate::{CompressionType, DeflateReader},
+    error::DecodeError,
+    format::{format_of, Format},
+    gzip::{CompressionMethod, GzipReader, MemberReader},
+    huffman_coding::{
+        build_litlen_distance_trees, get_fixed_tree, read_code_lengths, BlockCodes, LitLenToken,
+    },
+    options::DecodeOptions,
+    tracking_writer::TrackingWriter,
+    warning::Warning,
+    ZLIB_BODY_HINT,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const STORED_CHUNK_SIZE: usize = 4096;
+
+enum State {
+    Header,
+    Block,
+    Stored { left: usize },
+    Compressed { codes: BlockCodes },
+    Footer,
+    Done,
+}
+
+/// Counters describing the work done by a `GzDecoder`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Bits consumed by the DEFLATE bit reader, excluding gzip headers.
+    pub bits_read: u64,
+    /// Literal, length and end-of-block symbols decoded.
+    pub symbols: u64,
+    /// DEFLATE blocks started, across all members.
+    pub blocks: u64,
+    /// Time spent in `decode_into_buffer`, if `DecodeOptions::measure_time`
+    /// is set.
+    pub elapsed: Option<Duration>,
+}
+
+/// A step of decoding reported by `GzDecoder::next_event`, for tools that
+/// look at the block structure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeEvent {
+    BlockStart(CompressionType),
+    /// Everything the block decoded to, stored blocks included, if not
+    /// empty.
+    Chunk(Vec<u8>),
+    BlockEnd,
+}
+
+/// Pull-based gzip decoder. Decoding is suspended between symbols whenever
+/// the caller's buffer is full, so any buffer size works.
+///
+/// It implements `Read`, so it can feed streaming consumers directly, e.g.
+/// `tar::Archive::new(GzDecoder::new(reader))` for a `.tar.gz`, without
+/// materializing the whole decompressed output.
+pub struct GzDecoder<R> {
+    deflate_reader: DeflateReader<R>,
+    writer: TrackingWriter<Vec<u8>>,
+    options: DecodeOptions,
+    state: State,
+    warnings: Vec<Warning>,
+    stats: DecodeStats,
+    member_blocks: u64,
+    output_before: u64,
+    members_read: usize,
+    zlib_body: bool,
+    /// Bytes at the front of the pending output that may be handed out. The
+    /// rest belongs to a member still waiting for its footer check, if
+    /// `buffer_until_verified` is set.
+    released: usize,
+    record_events: bool,
+    events: VecDeque<DecodeEvent>,
+    #[cfg(feature = "dump-trees")]
+    tree_dumps: Vec<crate::huffman_coding::TreeDump>,
+}
+
+impl<R: BufRead> GzDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DecodeOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: DecodeOptions) -> Self {
+        Self {
+            deflate_reader: DeflateReader::new(BitReader::new(reader)),
+            writer: TrackingWriter::new(vec![]).with_max_distance(options.max_window_distance),
+            options,
+            state: State::Header,
+            warnings: vec![],
+            stats: DecodeStats::default(),
+            member_blocks: 0,
+            output_before: 0,
+            members_read: 0,
+            zlib_body: false,
+            released: 0,
+            record_events: false,
+            events: VecDeque::new(),
+            #[cfg(feature = "dump-trees")]
+            tree_dumps: vec![],
+        }
+    }
+
+    /// Warnings raised by the members decoded so far.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Trees of the dynamic blocks decoded so far, in stream order.
+    #[cfg(feature = "dump-trees")]
+    pub fn tree_dumps(&self) -> &[crate::huffman_coding::TreeDump] {
+        &self.tree_dumps
+    }
+
+    /// Statistics for everything decoded up to the last `decode_into_buffer`
+    /// call.
+    pub fn last_stats(&self) -> DecodeStats {
+        DecodeStats {
+            bits_read: self.deflate_reader.bits_read(),
+            ..self.stats
+        }
+    }
+
+    /// Decode into `out` until it is full or the input is exhausted. Returns
+    /// the number of bytes written; 0 means the stream is over, unless `out`
+    /// is empty, in which case nothing is decoded and 0 is returned right
+    /// away.
+    pub fn decode_into_buffer(&mut self, out: &mut [u8]) -> Result<usize, DecodeError> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        let start = self.options.measure_time.then(Instant::now);
+        let result = self.fill(out.len());
+        if let Some(start) = start {
+            *self.stats.elapsed.get_or_insert_default() += start.elapsed();
+        }
+        result?;
+        let size = self.ready().min(out.len());
+        let pending = self.writer.inner_mut();
+        out[..size].copy_from_slice(&pending[..size]);
+        pending.drain(..size);
+        self.released = self.released.saturating_sub(size);
+        Ok(size)
+    }
+
+    /// Decode up to the next block boundary and report it. The output comes
+    /// as one `Chunk` per block, as soon as the block ends, before the
+    /// member's footer is checked. Don't mix with `decode_into_buffer`.
+    pub fn next_event(&mut self) -> Option<Result<DecodeEvent, DecodeError>> {
+        self.record_events = true;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if matches!(self.state, State::Done) {
+                return None;
+            }
+            if let Err(err) = self.step() {
+                return Some(Err(err.into()));
+            }
+        }
+    }
+
+    /// Iterate over `next_event` until the stream is over.
+    pub fn events(mut self) -> impl Iterator<Item = Result<DecodeEvent, DecodeError>> {
+        std::iter::from_fn(move || self.next_event())
+    }
+
+    fn end_block(&mut self) {
+        if !self.record_events {
+            return;
+        }
+        let data = std::mem::take(self.writer.inner_mut());
+        self.released = 0;
+        if !data.is_empty() {
+            self.events.push_back(DecodeEvent::Chunk(data));
+        }
+        self.events.push_back(DecodeEvent::BlockEnd);
+    }
+
+    /// Pending bytes that may be handed out.
+    fn ready(&mut self) -> usize {
+        match self.options.buffer_until_verified {
+            Some(_) => self.released,
+            None => self.writer.inner_mut().len(),
+        }
+    }
+
+    fn fill(&mut self, size: usize) -> Result<()> {
+        while self.ready() < size && !matches!(self.state, State::Done) {
+            self.step()?;
+            self.check_held_size()?;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<()> {
+        self.check_compressed_size()?;
+        let state = std::mem::replace(&mut self.state, State::Done);
+        let in_body = matches!(
+            state,
+            State::Block | State::Stored { .. } | State::Compressed { .. }
+        );
+        let next = match state {
+            State::Header => self.read_header(),
+            State::Block => self.read_block_header(),
+            State::Stored { left } => self.read_stored(left),
+            State::Compressed { codes } => self.read_symbol(codes),
+            State::Footer => self.read_footer(),
+            State::Done => Ok(State::Done),
+        };
+        self.state = next.map_err(|err| match in_body && self.zlib_body {
+            true => err.context(ZLIB_BODY_HINT),
+            false => err,
+        })?;
+        Ok(())
+    }
+
+    fn read_header(&mut self) -> Result<State> {
+        check_checksum_policy(&self.options)?;
+        let reader = self.deflate_reader.reader().borrow_reader_from_boundary();
+        let mut gzip_reader =
+            GzipReader::with_options(reader, self.options.clone()).resume(self.members_read);
+        let (header, _flags) = match gzip_reader.read_header() {
+            Some(member) => member?,
+            None => return Ok(State::Done),
+        };
+        self.members_read += 1;
+        self.warnings.append(&mut gzip_reader.take_warnings());
+        if let CompressionMethod::Unknown(method) = header.compression_method {
+            bail!(DecodeError::UnsupportedMethod(method))
+        }
+        gzip_reader.check_body_present()?;
+        // Best effort, like `decode_members`: only buffered bytes are looked at.
+        self.zlib_body = format_of(gzip_reader.reader().fill_buf()?) == Format::Zlib;
+        self.deflate_reader.restart();
+        self.output_before += self.writer.byte_count();
+        self.writer.reset();
+        self.member_blocks = 0;
+        Ok(State::Block)
+    }
+
+    fn read_block_header(&mut self) -> Result<State> {
+        let (header, reader) = match self.deflate_reader.next_block() {
+            Some(block) => block?,
+            None => return Ok(State::Footer),
+        };
+        self.stats.blocks += 1;
+        self.member_blocks += 1;
+        check_block_count(&self.options, self.member_blocks)?;
+        if self.record_events {
+            let event = DecodeEvent::BlockStart(header.compression_type);
+            self.events.push_back(event);
+        }
+        Ok(match header.compression_type {
+            CompressionType::Uncompressed => {
+                let mut len_nlen = [0u8; 4];
+                reader.read_aligned(&mut len_nlen)?;
+                let len = LittleEndian::read_u16(&len_nlen[..2]);
+                let nlen = LittleEndian::read_u16(&len_nlen[2..]);
+                ensure!(
+                    len == !nlen || !self.options.verify_stored_nlen,
+                    "nlen check failed"
+                );
+                State::Stored { left: len as usize }
+            }
+            CompressionType::FixedTree => {
+                let codes = get_fixed_tree(self.options.huffman_table_budget);
+                State::Compressed { codes }
+            }
+            CompressionType::DynamicTree => {
+                let (lengths, hlit) = read_code_lengths(reader, self.options.huffman_table_budget)?;
+                let codes = build_litlen_distance_trees(
+                    &lengths[..hlit],
+                    &lengths[hlit..],
+                    self.options.huffman_table_budget,
+                )?;
+                #[cfg(feature = "dump-trees")]
+                self.tree_dumps.push(crate::huffman_coding::TreeDump::new(
+                    lengths[..hlit].to_vec(),
+                    lengths[hlit..].to_vec(),
+                ));
+                State::Compressed { codes }
+            }
+        })
+    }
+
+    fn read_stored(&mut self, left: usize) -> Result<State> {
+        if left == 0 {
+            self.end_block();
+            return Ok(State::Block);
+        }
+        let mut buf = [0u8; STORED_CHUNK_SIZE];
+        let chunk = left.min(buf.len());
+        self.deflate_reader
+            .reader()
+            .read_aligned(&mut buf[..chunk])?;
+        self.check_output_size(chunk)?;
+        self.writer.write_all(&buf[..chunk])?;
+        Ok(State::Stored { left: left - chunk })
+    }
+
+    fn read_symbol(&mut self, codes: BlockCodes) -> Result<State> {
+        let reader = self.deflate_reader.reader();
+        let symbol = codes.litlen().read_symbol(reader)?;
+        self.stats.symbols += 1;
+        match symbol {
+            LitLenToken::Literal(byte) => {
+                self.check_output_size(1)?;
+                self.writer.write_all(&[byte])?
+            }
+            LitLenToken::Length { base, extra_bits } => {
+                let len = base + reader.read_bits(extra_bits)?.bits();
+                let dist_token = codes.dist().read_symbol(reader)?;
+                let dist = dist_token.base + reader.read_bits(dist_token.extra_bits)?.bits();
+                self.check_output_size(len as usize)?;
+                self.writer.write_previous(dist as usize, len as usize)?;
+            }
+            LitLenToken::EndOfBlock => {
+                self.end_block();
+                return Ok(State::Block);
+            }
+        }
+        Ok(State::Compressed { codes })
+    }
+
+    fn read_footer(&mut self) -> Result<State> {
+        let reader = self.deflate_reader.reader().borrow_reader_from_boundary();
+        let (footer, _reader) = MemberReader::new(reader).read_footer()?;
+        let keep = check_footer(
+            &self.options,
+            &footer,
+            &self.writer,
+            self.members_read - 1,
+            &mut self.warnings,
+        )?;
+        let pending = self.writer.inner_mut();
+        match keep {
+            true => self.released = pending.len(),
+            false => pending.truncate(self.released),
+        }
+        Ok(State::Header)
+    }
+
+    /// Enforce `buffer_until_verified` on the member waiting for its footer.
+    fn check_held_size(&mut self) -> Result<()> {
+        let Some(max_held) = self.options.buffer_until_verified else {
+            return Ok(());
+        };
+        let held = self.writer.inner_mut().len() - self.released;
+        ensure!(held <= max_held, "buffer_until_verified limit exceeded");
+        Ok(())
+    }
+
+    fn check_output_size(&self, more: usize) -> Result<()> {
+        let produced = self.output_before + self.writer.byte_count();
+        check_output_size(&self.options, produced, more)
+    }
+
+    fn check_compressed_size(&self) -> Result<()> {
+        match self.options.max_compressed_bytes {
+            Some(max) if self.deflate_reader.bytes_read() > max => {
+                Err(anyhow!("compressed size limit exceeded"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.decode_into_buffer(buf)?)
+    }
+}
@@ -1,6 +1,6 @@
 #![forbid(unsafe_code)]
 
-use std::io::BufRead;
+use std::{fmt, io::BufRead};
 
 use anyhow::{anyhow, Result};
 
@@ -10,17 +10,26 @@ use crate::bit_reader::BitReader;
 
 #[derive(Debug)]
 pub struct BlockHeader {
-    pub is_final: bool,
     pub compression_type: CompressionType,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CompressionType {
     Uncompressed = 0,
     FixedTree = 1,
     DynamicTree = 2,
 }
 
+impl fmt::Display for CompressionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uncompressed => write!(f, "uncompressed"),
+            Self::FixedTree => write!(f, "fixed huffman tree"),
+            Self::DynamicTree => write!(f, "dynamic huffman tree"),
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct DeflateReader<T> {
@@ -36,9 +45,22 @@ impl<T: BufRead> DeflateReader<T> {
         }
     }
 
-    // pub fn reader(&mut self) -> &mut BitReader<T> {
-    //     &mut self.bit_reader
-    // }
+    pub fn reader(&mut self) -> &mut BitReader<T> {
+        &mut self.bit_reader
+    }
+
+    /// Expect a new DEFLATE stream, e.g. the body of the next gzip member.
+    pub fn restart(&mut self) {
+        self.data_left = true;
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bit_reader.bytes_read()
+    }
+
+    pub fn bits_read(&self) -> u64 {
+        self.bit_reader.bits_read()
+    }
 
     pub fn next_block(&mut self) -> Option<Result<(BlockHeader, &mut BitReader<T>)>> {
         // println!("getting block header");
@@ -71,12 +93,23 @@ impl<T: BufRead> DeflateReader<T> {
             Err(err) => return Some(Err(anyhow::Error::new(err))),
         };
         // println!("got normal block type");
-        Some(Ok((
-            BlockHeader {
-                is_final: !self.data_left,
-                compression_type,
-            },
-            &mut self.bit_reader,
-        )))
+        Some(Ok((BlockHeader { compression_type }, &mut self.bit_reader)))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_type_display() {
+        assert_eq!(CompressionType::Uncompressed.to_string(), "uncompressed");
+        assert_eq!(CompressionType::FixedTree.to_string(), "fixed huffman tree");
+        assert_eq!(
+            CompressionType::DynamicTree.to_string(),
+            "dynamic huffman tree"
+        );
     }
 }
@@ -0,0 +1,206 @@
+#![forbid(unsafe_code)]
+
+use std::{fmt, io, str::Utf8Error, string::FromUtf8Error};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Error returned by the public API, to match on e.g. a checksum mismatch
+/// versus truncated input. Decoding failures without a variant of their own
+/// are `Other`.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The stream does not start with the gzip magic bytes, so it likely
+    /// isn't gzip at all.
+    BadMagic,
+    /// A member after the first one lacks the gzip magic bytes, so the file
+    /// is damaged.
+    CorruptMemberMagic,
+    /// A member uses a compression method other than DEFLATE. Its body can't
+    /// be decoded, nor skipped, so decoding stops there; the output of the
+    /// members before it is complete.
+    UnsupportedMethod(u8),
+    /// The CRC32 in a member's footer doesn't match the decoded data.
+    Crc32Mismatch { expected: u32, found: u32 },
+    /// The ISIZE in a member's footer doesn't match the decoded size modulo
+    /// 2^32.
+    LengthMismatch { expected: u32, found: u64 },
+    /// A back-reference reaches further back than the history available.
+    BadDistance { distance: usize, available: usize },
+    /// The input ended early.
+    Truncated,
+    /// A header's FHCRC doesn't match the header.
+    HeaderCrcMismatch(HeaderCrcMismatch),
+    /// The output would grow past `DecodeOptions::max_output_bytes`.
+    OutputLimitExceeded(OutputLimitExceeded),
+    /// The output doesn't fit into the slice given to
+    /// `decompress_into_slice`.
+    OutputTooSmall { capacity: usize },
+    /// The output asked for as text isn't valid UTF-8.
+    InvalidUtf8(Utf8Error),
+    /// Reading the input or writing the output failed.
+    Io(io::Error),
+    /// Any other malformed input or rejected option, e.g. an invalid block
+    /// type. Holds the message along with its context.
+    Other(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a gzip stream: wrong id values"),
+            Self::CorruptMemberMagic => write!(f, "corrupt member magic: wrong id values"),
+            Self::UnsupportedMethod(method) => {
+                write!(f, "unsupported compression method: {}", method)
+            }
+            Self::Crc32Mismatch { expected, found } => write!(
+                f,
+                "crc32 check failed: expected={:#010x} actual={:#010x}",
+                expected, found
+            ),
+            Self::LengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "length check failed: expected={} actual={}",
+                    expected, found
+                )?;
+                if *expected == 0 {
+                    write!(f, " (footer claims an empty member)")?;
+                }
+                Ok(())
+            }
+            Self::BadDistance {
+                distance,
+                available,
+            } => write!(
+                f,
+                "bad dist: {} with {} bytes of history",
+                distance, available
+            ),
+            Self::Truncated => write!(f, "input truncated"),
+            Self::HeaderCrcMismatch(mismatch) => mismatch.fmt(f),
+            Self::OutputLimitExceeded(exceeded) => exceeded.fmt(f),
+            Self::OutputTooSmall { capacity } => {
+                write!(f, "output doesn't fit into {} bytes", capacity)
+            }
+            Self::InvalidUtf8(err) => write!(f, "output is not valid utf-8: {}", err),
+            Self::Io(err) => err.fmt(f),
+            Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+impl From<FromUtf8Error> for DecodeError {
+    fn from(err: FromUtf8Error) -> Self {
+        Self::InvalidUtf8(err.utf8_error())
+    }
+}
+
+/// The decoder works with `anyhow` internally; its errors are classified
+/// here, at the public API boundary.
+impl From<anyhow::Error> for DecodeError {
+    fn from(err: anyhow::Error) -> Self {
+        let truncated = err.chain().any(|cause| {
+            cause
+                .downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::UnexpectedEof)
+        });
+        if truncated {
+            return Self::Truncated;
+        }
+        let err = match err.downcast::<Self>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        match err.downcast::<io::Error>() {
+            Ok(err) => Self::Io(err),
+            Err(err) => Self::Other(format!("{:#}", err)),
+        }
+    }
+}
+
+/// For I/O adapters: I/O errors are passed through, truncated input becomes
+/// `UnexpectedEof` and everything else `InvalidData`.
+impl From<DecodeError> for io::Error {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::Io(err) => err,
+            DecodeError::Truncated => io::Error::new(io::ErrorKind::UnexpectedEof, err),
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A header's FHCRC doesn't match the header, see
+/// `DecodeError::HeaderCrcMismatch`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderCrcMismatch {
+    /// CRC16 stored in the header.
+    pub stored: u16,
+    /// CRC16 computed over the header.
+    pub computed: u16,
+    /// Size of the header in bytes, including the CRC16.
+    pub header_len: usize,
+    /// The header exactly as read, filled in by `decompress_seekable`.
+    pub raw_header: Option<Vec<u8>>,
+}
+
+impl fmt::Display for HeaderCrcMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "header crc16 check failed: stored={:#06x} computed={:#06x}",
+            self.stored, self.computed
+        )?;
+        if let Some(raw_header) = &self.raw_header {
+            write!(f, ", header bytes: {:02x?}", raw_header)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HeaderCrcMismatch {}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Decoding stopped because the output would grow past
+/// `DecodeOptions::max_output_bytes`, see `DecodeError::OutputLimitExceeded`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputLimitExceeded {
+    /// The configured limit.
+    pub limit: u64,
+    /// Bytes written to the output before decoding stopped, across all
+    /// members. Never more than `limit`.
+    pub produced: u64,
+}
+
+impl fmt::Display for OutputLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "output size limit exceeded: {} bytes produced, limit is {}",
+            self.produced, self.limit
+        )
+    }
+}
+
+impl std::error::Error for OutputLimitExceeded {}
@@ -0,0 +1,166 @@
+#![forbid(unsafe_code)]
+
+use std::io::{self, BufRead, Cursor, Read};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::error::DecodeError;
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+pub const ZLIB_CM_DEFLATE: u8 = 8;
+pub const ZLIB_MAX_CINFO: u8 = 7;
+pub const ZLIB_FDICT: u8 = 1 << 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Zlib,
+    /// Anything else, assumed to be a bare DEFLATE stream.
+    Raw,
+}
+
+/// A reader with the bytes looked at by `detect_format` or
+/// `peek_zlib_header` put back in front of it.
+pub type Peeked<R> = io::Chain<Cursor<Vec<u8>>, R>;
+
+/// Take up to `len` bytes off the front of `reader`, reading as often as it
+/// takes, and put them back in front of it. Works whatever the reader's
+/// buffer capacity.
+fn peek<R: BufRead>(mut reader: R, len: usize) -> io::Result<Peeked<R>> {
+    let mut prefix = Vec::with_capacity(len);
+    (&mut reader).take(len as u64).read_to_end(&mut prefix)?;
+    Ok(Cursor::new(prefix).chain(reader))
+}
+
+/// Guess the container of the stream from its first two bytes. Returns the
+/// reader with those bytes still to be read.
+pub fn detect_format<R: BufRead>(reader: R) -> Result<(Format, Peeked<R>), DecodeError> {
+    let peeked = peek(reader, 2)?;
+    let format = format_of(peeked.get_ref().0.get_ref());
+    Ok((format, peeked))
+}
+
+/// Guess the container from the bytes at hand, `Raw` if there are fewer than
+/// two.
+pub(crate) fn format_of(prefix: &[u8]) -> Format {
+    match *prefix {
+        [cmf, flg, ..] if [cmf, flg] == GZIP_MAGIC => Format::Gzip,
+        [cmf, flg, ..] if is_zlib_header(cmf, flg) => Format::Zlib,
+        _ => Format::Raw,
+    }
+}
+
+fn is_zlib_header(cmf: u8, flg: u8) -> bool {
+    cmf & 0x0f == ZLIB_CM_DEFLATE
+        && cmf >> 4 <= ZLIB_MAX_CINFO
+        && (cmf as u16 * 256 + flg as u16).is_multiple_of(31)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ZlibHeader {
+    pub cmf: u8,
+    pub flg: u8,
+    /// Adler-32 of the preset dictionary the stream needs, if FDICT is set.
+    pub dict_id: Option<u32>,
+    /// Base-two logarithm of the LZ77 window size.
+    pub window_bits: u8,
+}
+
+/// Parse the zlib header at the start of the stream, or `None` if the stream
+/// doesn't start with one. Returns the reader with the header still to be
+/// read.
+pub fn peek_zlib_header<R: BufRead>(
+    reader: R,
+) -> Result<(Option<ZlibHeader>, Peeked<R>), DecodeError> {
+    let peeked = peek(reader, 6)?;
+    let buf = peeked.get_ref().0.get_ref();
+    if buf.len() < 2 || !is_zlib_header(buf[0], buf[1]) {
+        return Ok((None, peeked));
+    }
+    let (cmf, flg) = (buf[0], buf[1]);
+    let dict_id = match flg & ZLIB_FDICT {
+        0 => None,
+        _ if buf.len() < 6 => return Err(DecodeError::Truncated),
+        _ => Some(BigEndian::read_u32(&buf[2..6])),
+    };
+    let header = ZlibHeader {
+        cmf,
+        flg,
+        dict_id,
+        window_bits: (cmf >> 4) + 8,
+    };
+    Ok((Some(header), peeked))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(data: &[u8]) -> Format {
+        super::detect_format(data).unwrap().0
+    }
+
+    #[test]
+    fn detect_format() -> Result<(), DecodeError> {
+        let gzip: &[u8] = &[0x1f, 0x8b, 8, 0];
+        let (format, mut peeked) = super::detect_format(gzip)?;
+        assert_eq!(format, Format::Gzip);
+        let mut rest = vec![];
+        peeked.read_to_end(&mut rest)?;
+        assert_eq!(rest, gzip);
+
+        for zlib in [[0x78, 0x01], [0x78, 0x9c], [0x78, 0xda], [0x08, 0x1d]] {
+            assert_eq!(detected(&zlib), Format::Zlib);
+        }
+
+        assert_eq!(detected(&[0x78, 0x9d, 0]), Format::Raw);
+        assert_eq!(detected(&[0x01, 0x00, 0x00, 0xff, 0xff]), Format::Raw);
+        assert_eq!(detected(&[0x1f]), Format::Raw);
+
+        // A reader buffering a single byte at a time still shows both.
+        let reader = io::BufReader::with_capacity(1, gzip);
+        assert_eq!(super::detect_format(reader)?.0, Format::Gzip);
+
+        Ok(())
+    }
+
+    #[test]
+    fn peek_zlib_header() -> Result<(), DecodeError> {
+        let zlib: &[u8] = &[0x78, 0x9c, 0x03, 0x00];
+        let (header, mut peeked) = super::peek_zlib_header(zlib)?;
+        let header = header.unwrap();
+        assert_eq!(header.dict_id, None);
+        assert_eq!(header.window_bits, 15);
+        let mut rest = vec![];
+        peeked.read_to_end(&mut rest)?;
+        assert_eq!(rest, zlib);
+
+        let fdict: &[u8] = &[0x78, 0xbb, 0x12, 0x34, 0x56, 0x78, 0x03, 0x00];
+        let header = super::peek_zlib_header(fdict)?.0.unwrap();
+        assert_eq!((header.cmf, header.flg), (0x78, 0xbb));
+        assert_eq!(header.dict_id, Some(0x12345678));
+
+        // Only a real end of input counts as truncation.
+        let reader = io::BufReader::with_capacity(1, fdict);
+        let header = super::peek_zlib_header(reader)?.0.unwrap();
+        assert_eq!(header.dict_id, Some(0x12345678));
+        assert!(super::peek_zlib_header(&fdict[..4]).is_err());
+
+        assert_eq!(
+            super::peek_zlib_header(&[0x08, 0x1d][..])?
+                .0
+                .map(|h| h.window_bits),
+            Some(8)
+        );
+        assert_eq!(super::peek_zlib_header(&[0x1f, 0x8b][..])?.0, None);
+
+        Ok(())
+    }
+}
@@ -1,10 +1,24 @@
 #![forbid(unsafe_code)]
 
-use std::io::BufRead;
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{anyhow, Result};
-use byteorder::{LittleEndian, ReadBytesExt};
-use crc::Crc;
+use anyhow::{anyhow, bail, Result};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use crc::Digest;
+
+use crate::{
+    bit_reader::BitReader,
+    deflate::DeflateReader,
+    error::{DecodeError, HeaderCrcMismatch},
+    inflate_blocks,
+    options::DecodeOptions,
+    tracking_writer::{TrackingWriter, CRC_CFG},
+    warning::Warning,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -13,19 +27,49 @@ const ID2: u8 = 0x8b;
 
 const CM_DEFLATE: u8 = 8;
 
+/// An extra field subfield: its SI1 SI2 id and its data.
+type Subfield<'a> = ([u8; 2], &'a [u8]);
+/// A member's header, decoded data and footer.
+type DecodedMember = (MemberHeader, Vec<u8>, MemberFooter);
+
 const FTEXT_OFFSET: u8 = 0;
 const FHCRC_OFFSET: u8 = 1;
 const FEXTRA_OFFSET: u8 = 2;
 const FNAME_OFFSET: u8 = 3;
 const FCOMMENT_OFFSET: u8 = 4;
+const FRESERVED_MASK: u8 = 0b1110_0000;
+
+const OS_NAMES: [&str; 14] = [
+    "FAT",
+    "Amiga",
+    "VMS",
+    "Unix",
+    "VM/CMS",
+    "Atari TOS",
+    "HPFS",
+    "Macintosh",
+    "Z-System",
+    "CP/M",
+    "TOPS-20",
+    "NTFS",
+    "QDOS",
+    "Acorn RISCOS",
+];
+const OS_UNKNOWN: u8 = 255;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemberHeader {
     pub compression_method: CompressionMethod,
+    /// Raw MTIME: seconds since the Unix epoch in UTC, 0 if not recorded.
+    /// See `mtime` for it as an `Option<SystemTime>`.
     pub modification_time: u32,
+    /// Raw FEXTRA field. `Some(vec![])` when the flag is set with XLEN 0, so
+    /// it round-trips through `write_to`.
     pub extra: Option<Vec<u8>>,
+    /// FNAME and FCOMMENT, decoded from ISO-8859-1 as RFC 1952 specifies.
     pub name: Option<String>,
     pub comment: Option<String>,
     pub extra_flags: u8,
@@ -35,34 +79,117 @@ pub struct MemberHeader {
 }
 
 impl MemberHeader {
-    pub fn crc16(&self) -> u16 {
-        let crc = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
-        let mut digest = crc.digest();
-
-        digest.update(&[ID1, ID2, self.compression_method.into(), self.flags().0]);
-        digest.update(&self.modification_time.to_le_bytes());
-        digest.update(&[self.extra_flags, self.os]);
+    /// Serialize the header, including the FHCRC if `has_crc` is set.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let fields = self.encode_fields();
+        writer.write_all(&fields)?;
+        if self.has_crc {
+            writer.write_u16::<LittleEndian>((CRC_CFG.checksum(&fields) & 0xffff) as u16)?;
+        }
+        Ok(())
+    }
 
+    /// Everything `write_to` writes but the FHCRC.
+    fn encode_fields(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(self.encoded_len());
+        encoded.extend_from_slice(&[
+            ID1,
+            ID2,
+            self.compression_method.into(),
+            self.flags().bits(),
+        ]);
+        encoded.extend_from_slice(&self.modification_time.to_le_bytes());
+        encoded.extend_from_slice(&[self.extra_flags, self.os]);
         if let Some(extra) = &self.extra {
-            digest.update(&(extra.len() as u16).to_le_bytes());
-            digest.update(extra);
+            encoded.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            encoded.extend_from_slice(extra);
+        }
+        for field in [&self.name, &self.comment].into_iter().flatten() {
+            encoded.extend_from_slice(&latin1_bytes(field));
+            encoded.push(0);
+        }
+        encoded
+    }
+
+    /// MTIME read as UTC seconds since the Unix epoch, as RFC 1952 defines
+    /// it, or `None` if no time was recorded. Some old tools wrote local time
+    /// instead; converting that is left to the caller.
+    pub fn mtime(&self) -> Option<SystemTime> {
+        match self.modification_time {
+            0 => None,
+            secs => Some(UNIX_EPOCH + Duration::from_secs(secs as u64)),
         }
+    }
 
-        if let Some(name) = &self.name {
-            digest.update(name.as_bytes());
-            digest.update(&[0]);
+    /// Split the extra field into `(SI1 SI2, data)` subfields. An absent or
+    /// empty extra field has none.
+    pub fn extra_subfields(&self) -> Result<Vec<Subfield<'_>>, DecodeError> {
+        let mut rest = self.extra.as_deref().unwrap_or_default();
+        let mut subfields = vec![];
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return Err(DecodeError::Other(
+                    "truncated extra subfield header".to_string(),
+                ));
+            }
+            let len = LittleEndian::read_u16(&rest[2..4]) as usize;
+            if rest.len() < 4 + len {
+                return Err(DecodeError::Other(
+                    "extra subfield longer than the extra field".to_string(),
+                ));
+            }
+            subfields.push(([rest[0], rest[1]], &rest[4..4 + len]));
+            rest = &rest[4 + len..];
         }
+        Ok(subfields)
+    }
 
-        if let Some(comment) = &self.comment {
-            digest.update(comment.as_bytes());
-            digest.update(&[0]);
+    /// Name of the OS the member was written on, via `options.os_name_fn`
+    /// first and `OperatingSystem::name` otherwise.
+    pub fn os_name(&self, options: &DecodeOptions) -> Option<&'static str> {
+        match options.os_name_fn.and_then(|os_name| os_name(self.os)) {
+            Some(name) => Some(name),
+            None => self.os().name(),
         }
+    }
 
-        (digest.finalize() & 0xffff) as u16
+    /// The OS byte decoded, see `OperatingSystem`.
+    pub fn os(&self) -> OperatingSystem {
+        OperatingSystem::from(self.os)
+    }
+
+    /// Size of the header as `write_to` encodes it.
+    pub fn encoded_len(&self) -> usize {
+        let mut len = 10;
+        if let Some(extra) = &self.extra {
+            len += 2 + extra.len();
+        }
+        for field in [&self.name, &self.comment].into_iter().flatten() {
+            len += field.chars().count() + 1;
+        }
+        if self.has_crc {
+            len += 2;
+        }
+        len
+    }
+
+    /// The last path component of FNAME, for tools that create a file by
+    /// that name. Mitigates path traversal: directories, `/` and `\` alike,
+    /// are dropped, and a name ending in `.`, `..` or a separator gives `None`.
+    pub fn safe_name(&self) -> Option<String> {
+        let name = self.name.as_deref()?.rsplit(['/', '\\']).next()?;
+        match name {
+            "" | "." | ".." => None,
+            name => Some(name.to_owned()),
+        }
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
     }
 
     pub fn flags(&self) -> MemberFlags {
-        let mut flags = MemberFlags(0);
+        let mut flags = MemberFlags::from_bits(0);
         flags.set_is_text(self.is_text);
         flags.set_has_crc(self.has_crc);
         flags.set_has_extra(self.extra.is_some());
@@ -74,7 +201,8 @@ impl MemberHeader {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CompressionMethod {
     Deflate,
     Unknown(u8),
@@ -89,6 +217,15 @@ impl From<u8> for CompressionMethod {
     }
 }
 
+impl fmt::Display for CompressionMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deflate => write!(f, "deflate"),
+            Self::Unknown(x) => write!(f, "unknown ({})", x),
+        }
+    }
+}
+
 impl From<CompressionMethod> for u8 {
     fn from(method: CompressionMethod) -> u8 {
         match method {
@@ -98,13 +235,116 @@ impl From<CompressionMethod> for u8 {
     }
 }
 
+/// Encode FNAME or FCOMMENT as ISO-8859-1. Characters it lacks become `?`.
+fn latin1_bytes(field: &str) -> Vec<u8> {
+    field
+        .chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The OS a member was written on, with the values RFC 1952 assigns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OperatingSystem {
+    Fat,
+    Amiga,
+    Vms,
+    Unix,
+    VmCms,
+    AtariTos,
+    Hpfs,
+    Macintosh,
+    ZSystem,
+    CpM,
+    Tops20,
+    Ntfs,
+    Qdos,
+    AcornRiscos,
+    /// 255 for "unknown", or a value RFC 1952 doesn't assign.
+    Unknown(u8),
+}
+
+impl OperatingSystem {
+    /// The name RFC 1952 gives the value, `None` for unassigned ones.
+    pub fn name(&self) -> Option<&'static str> {
+        match *self {
+            Self::Unknown(OS_UNKNOWN) => Some("unknown"),
+            Self::Unknown(_) => None,
+            os => Some(OS_NAMES[u8::from(os) as usize]),
+        }
+    }
+}
+
+impl From<u8> for OperatingSystem {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Fat,
+            1 => Self::Amiga,
+            2 => Self::Vms,
+            3 => Self::Unix,
+            4 => Self::VmCms,
+            5 => Self::AtariTos,
+            6 => Self::Hpfs,
+            7 => Self::Macintosh,
+            8 => Self::ZSystem,
+            9 => Self::CpM,
+            10 => Self::Tops20,
+            11 => Self::Ntfs,
+            12 => Self::Qdos,
+            13 => Self::AcornRiscos,
+            x => Self::Unknown(x),
+        }
+    }
+}
+
+impl From<OperatingSystem> for u8 {
+    fn from(os: OperatingSystem) -> u8 {
+        match os {
+            OperatingSystem::Fat => 0,
+            OperatingSystem::Amiga => 1,
+            OperatingSystem::Vms => 2,
+            OperatingSystem::Unix => 3,
+            OperatingSystem::VmCms => 4,
+            OperatingSystem::AtariTos => 5,
+            OperatingSystem::Hpfs => 6,
+            OperatingSystem::Macintosh => 7,
+            OperatingSystem::ZSystem => 8,
+            OperatingSystem::CpM => 9,
+            OperatingSystem::Tops20 => 10,
+            OperatingSystem::Ntfs => 11,
+            OperatingSystem::Qdos => 12,
+            OperatingSystem::AcornRiscos => 13,
+            OperatingSystem::Unknown(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for OperatingSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name(), self) {
+            (Some(name), _) => write!(f, "{}", name),
+            (None, os) => write!(f, "unknown ({})", u8::from(*os)),
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct MemberFlags(u8);
 
-#[allow(unused)]
 impl MemberFlags {
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
     fn bit(&self, n: u8) -> bool {
         (self.0 >> n) & 1 != 0
     }
@@ -160,73 +400,240 @@ impl MemberFlags {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemberFooter {
     pub data_crc32: u32,
     pub data_size: u32,
 }
 
+impl MemberFooter {
+    /// Check the decoded `size` and `crc32` against this footer.
+    pub fn verify(&self, size: u64, crc32: u32) -> Result<(), DecodeError> {
+        // ISIZE is the size modulo 2^32.
+        if self.data_size != size as u32 {
+            return Err(DecodeError::LengthMismatch {
+                expected: self.data_size,
+                found: size,
+            });
+        }
+        if self.data_crc32 != crc32 {
+            return Err(DecodeError::Crc32Mismatch {
+                expected: self.data_crc32,
+                found: crc32,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u32::<LittleEndian>(self.data_crc32)?;
+        writer.write_u32::<LittleEndian>(self.data_size)
+    }
+}
+
+/// Compute the footer a gzip member holding `data` must end with.
+pub fn make_footer(data: &[u8]) -> MemberFooter {
+    MemberFooter {
+        data_crc32: CRC_CFG.checksum(data),
+        data_size: data.len() as u32,
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct GzipReader<T> {
     reader: T,
+    options: DecodeOptions,
+    warnings: Vec<Warning>,
+    members_read: usize,
 }
 
 impl<T: BufRead> GzipReader<T> {
     pub fn new(reader: T) -> Self {
-        Self { reader }
+        Self::with_options(reader, DecodeOptions::default())
+    }
+
+    pub fn with_options(reader: T, options: DecodeOptions) -> Self {
+        Self {
+            reader,
+            options,
+            warnings: vec![],
+            members_read: 0,
+        }
     }
 
     pub fn reader(&mut self) -> &mut T {
         &mut self.reader
     }
 
-    fn read_string(&mut self) -> Result<String> {
-        let mut buffer = vec![];
-        self.reader.read_until(0, &mut buffer)?;
-        Ok(String::from_utf8(buffer)?)
+    /// Carry on after `members_read` members read by another `GzipReader`
+    /// over the same input, e.g. one per member over a reborrowed reader.
+    /// Errors and `stop_after_last_member` depend on whether a member came
+    /// before.
+    pub(crate) fn resume(mut self, members_read: usize) -> Self {
+        self.members_read = members_read;
+        self
+    }
+
+    /// Take the warnings collected from the headers read so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn check_header(&mut self, header: &MemberHeader) {
+        if let Some(mtime) = header.mtime() {
+            if mtime > SystemTime::now() {
+                self.warnings.push(Warning::FutureModificationTime(mtime));
+            }
+        }
     }
 
-    pub fn read_header(&mut self) -> Option<Result<(MemberHeader, MemberFlags)>> {
+    /// Fast path for the common header without any optional fields: only the
+    /// fixed MTIME, XFL and OS bytes remain.
+    fn read_minimal_header(
+        &mut self,
+        compression_method: CompressionMethod,
+        member_flags: MemberFlags,
+    ) -> Result<(MemberHeader, MemberFlags)> {
+        let mut fixed = [0u8; 6];
+        self.reader.read_exact(&mut fixed)?;
+        let member_header = MemberHeader {
+            compression_method,
+            modification_time: LittleEndian::read_u32(&fixed[..4]),
+            extra: None,
+            name: None,
+            comment: None,
+            extra_flags: fixed[4],
+            os: fixed[5],
+            has_crc: false,
+            is_text: false,
+        };
+        Ok((member_header, member_flags))
+    }
+
+    /// Read the next header only if the input goes on with the gzip magic.
+    /// The second byte may only show up once the first one is consumed, so a
+    /// 0x1f followed by anything but 0x8b is the one trailing byte read.
+    fn read_member_header_if_magic(&mut self) -> Option<Result<(MemberHeader, MemberFlags)>> {
+        if self.reader.fill_buf().ok()?.first() != Some(&ID1) {
+            return None;
+        }
+        self.reader.consume(1);
+        if matches!(self.reader.fill_buf(), Ok(buf) if buf.first().is_some_and(|&id2| id2 != ID2)) {
+            return None;
+        }
+        Some(self.read_member_header_after(ID1))
+    }
+
+    /// Fail with a clear message if the input ends right after a header,
+    /// rather than with an EOF from inside the first block.
+    pub fn check_body_present(&mut self) -> Result<(), DecodeError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Err(anyhow!("gzip header present but no DEFLATE data").into());
+        }
+        Ok(())
+    }
+
+    /// Iterate over the members, decoding one body per `next`. Each member
+    /// starts with an empty history window and is checked against its footer
+    /// before it is yielded.
+    pub fn members(self) -> Members<T> {
+        Members {
+            gzip_reader: self,
+            compressed_bytes: 0,
+            decompressed_bytes: 0,
+            failed: false,
+        }
+    }
+
+    pub fn read_header(&mut self) -> Option<Result<(MemberHeader, MemberFlags), DecodeError>> {
+        let member = match self.options.stop_after_last_member && self.members_read > 0 {
+            true => self.read_member_header_if_magic()?,
+            false => self.read_member_header()?,
+        };
+        self.members_read += 1;
+        if let Ok((header, _flags)) = &member {
+            self.check_header(header);
+        }
+        Some(member.map_err(DecodeError::from))
+    }
+
+    fn read_member_header(&mut self) -> Option<Result<(MemberHeader, MemberFlags)>> {
         let id1 = match self.reader.read_u8() {
             Ok(ok) => ok,
             _ => return None,
         };
-        let id2 = match self.reader.read_u8() {
-            Err(err) => return Some(Err(anyhow!(err))),
-            Ok(ok) => ok,
-        };
+        Some(self.read_member_header_after(id1))
+    }
+
+    /// The rest of `read_member_header` once the first byte is in, i.e. once
+    /// the input is known not to be over.
+    fn read_member_header_after(&mut self, id1: u8) -> Result<(MemberHeader, MemberFlags)> {
+        let id2 = self.reader.read_u8()?;
         if id1 != 31 || id2 != 139 {
-            return Some(Err(anyhow!("wrong id values")));
+            bail!(match self.members_read {
+                0 => DecodeError::BadMagic,
+                _ => DecodeError::CorruptMemberMagic,
+            });
         }
-        let compression_method = CompressionMethod::from(match self.reader.read_u8() {
-            Ok(ok) => ok,
-            Err(err) => return Some(Err(anyhow!(err))),
-        });
-        let member_flags = MemberFlags(self.reader.read_u8().unwrap());
-        let modification_time = self.reader.read_u32::<LittleEndian>().unwrap();
-        let extra_flags = self.reader.read_u8().unwrap();
-        let os = self.reader.read_u8().unwrap();
+        let compression_method = CompressionMethod::from(self.reader.read_u8()?);
+        let member_flags = MemberFlags::from_bits(self.reader.read_u8()?);
+        if member_flags.bits() & FRESERVED_MASK != 0 {
+            bail!("reserved flag bits set: {:#010b}", member_flags.bits());
+        }
+        if !member_flags.has_crc() && self.options.require_header_crc {
+            bail!("header crc16 is required but missing");
+        }
+        if member_flags.bits() == 0 {
+            return self.read_minimal_header(compression_method, member_flags);
+        }
+        let mut reader = HeaderReader {
+            inner: &mut self.reader,
+            digest: CRC_CFG.digest(),
+            len: 0,
+        };
+        reader.record(&[id1, id2, compression_method.into(), member_flags.bits()]);
+        let modification_time = reader.read_u32::<LittleEndian>()?;
+        let extra_flags = reader.read_u8()?;
+        let os = reader.read_u8()?;
+        let fixed_crc16 = reader.crc16();
         let mut extra = None;
         if member_flags.has_extra() {
-            // self.reader.read_u16::<LittleEndian>().ok()?;
-            let extra_len = self.reader.read_u16::<LittleEndian>().unwrap();
+            let extra_len = reader.read_u16::<LittleEndian>()?;
             let mut buffer = vec![0; extra_len as usize];
-            self.reader.read_exact(buffer.as_mut_slice()).unwrap();
+            reader.read_exact(buffer.as_mut_slice())?;
             extra = Some(buffer);
         }
         let name = match member_flags.has_name() {
-            true => Some(self.read_string().unwrap()),
+            true => Some(read_string(&mut reader)?),
             false => None,
         };
         let comment = match member_flags.has_comment() {
-            true => Some(self.read_string().unwrap()),
+            true => Some(read_string(&mut reader)?),
             false => None,
         };
         let has_crc = member_flags.has_crc();
         let is_text = member_flags.is_text();
 
-        let member_header = MemberHeader {
+        if has_crc {
+            // Checked against the bytes as read, not as `write_to` would
+            // encode the parsed fields.
+            let computed = reader.crc16();
+            let header_len = reader.len + 2;
+            let crc16 = reader.inner.read_u16::<LittleEndian>()?;
+            let lenient_match = self.options.lenient_header_crc && crc16 == fixed_crc16;
+            if crc16 != computed && !lenient_match {
+                bail!(DecodeError::HeaderCrcMismatch(HeaderCrcMismatch {
+                    stored: crc16,
+                    computed,
+                    header_len,
+                    raw_header: None,
+                }));
+            }
+        }
+
+        let mut member_header = MemberHeader {
             compression_method,
             modification_time,
             extra,
@@ -237,11 +644,157 @@ impl<T: BufRead> GzipReader<T> {
             has_crc,
             is_text,
         };
+        // Only after the CRC check, which has to cover the empty fields.
+        if self.options.empty_name_as_none {
+            for field in [&mut member_header.name, &mut member_header.comment] {
+                if field.as_deref() == Some("") {
+                    *field = None;
+                }
+            }
+        }
+        Ok((member_header, member_flags))
+    }
+}
+
+/// Read a NUL-terminated FNAME or FCOMMENT.
+fn read_string<R: BufRead>(reader: &mut R) -> Result<String> {
+    let mut buffer = vec![];
+    reader.read_until(0, &mut buffer)?;
+    if buffer.pop() != Some(0) {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "header truncated in a string field",
+        )
+        .into());
+    }
+    // RFC 1952 strings are ISO-8859-1, whose bytes are the first 256 code
+    // points.
+    Ok(buffer.into_iter().map(char::from).collect())
+}
+
+/// Passes reads through while keeping the CRC32 and count of the consumed
+/// header bytes, for the FHCRC check.
+struct HeaderReader<'a, T> {
+    inner: &'a mut T,
+    digest: Digest<'static, u32>,
+    len: usize,
+}
+
+impl<T> HeaderReader<'_, T> {
+    fn record(&mut self, data: &[u8]) {
+        self.digest.update(data);
+        self.len += data.len();
+    }
+
+    fn crc16(&self) -> u16 {
+        (self.digest.clone().finalize() & 0xffff) as u16
+    }
+}
+
+impl<T: BufRead> Read for HeaderReader<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.record(&buf[..size]);
+        Ok(size)
+    }
+}
 
-        if has_crc && self.reader.read_u16::<LittleEndian>().ok()? != member_header.crc16() {
-            return Some(Err(anyhow!("header crc16 check failed")));
+impl<T: BufRead> BufRead for HeaderReader<'_, T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.digest.update(&buf[..amt]);
+            self.len += amt;
         }
-        Some(Ok((member_header, member_flags)))
+        self.inner.consume(amt);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Iterator returned by `GzipReader::members`. Stops after the first error.
+pub struct Members<T> {
+    gzip_reader: GzipReader<T>,
+    compressed_bytes: u64,
+    decompressed_bytes: u64,
+    failed: bool,
+}
+
+impl<T: BufRead> Members<T> {
+    fn decode_member<W: Write>(
+        &mut self,
+        header: &MemberHeader,
+        output: W,
+    ) -> Result<MemberFooter> {
+        if let CompressionMethod::Unknown(method) = header.compression_method {
+            bail!(DecodeError::UnsupportedMethod(method))
+        }
+        self.gzip_reader.check_body_present()?;
+
+        let options = &self.gzip_reader.options;
+        let mut writer = TrackingWriter::new(output).with_max_distance(options.max_window_distance);
+        let mut deflate_reader = DeflateReader::new(BitReader::new(&mut self.gzip_reader.reader));
+        inflate_blocks(
+            &mut deflate_reader,
+            &mut writer,
+            options,
+            self.compressed_bytes,
+            self.decompressed_bytes,
+        )?;
+        self.compressed_bytes += deflate_reader.bytes_read();
+        self.decompressed_bytes += writer.byte_count();
+
+        let (footer, _reader) = MemberReader::new(&mut self.gzip_reader.reader).read_footer()?;
+        if options.verify_checksums {
+            footer.verify(writer.byte_count(), writer.crc32())?;
+        }
+        Ok(footer)
+    }
+
+    /// Decode the next member into `output` rather than a buffer of its own.
+    pub(crate) fn next_into<W: Write>(
+        &mut self,
+        output: W,
+    ) -> Option<Result<(MemberHeader, MemberFooter), DecodeError>> {
+        if self.failed {
+            return None;
+        }
+        let result = self
+            .gzip_reader
+            .read_header()?
+            .and_then(|(header, _flags)| {
+                let footer = self.decode_member(&header, output)?;
+                Ok((header, footer))
+            });
+        self.failed = result.is_err();
+        Some(result)
+    }
+
+    fn next_with_footer(&mut self) -> Option<Result<DecodedMember, DecodeError>> {
+        let mut data = vec![];
+        let member = self.next_into(&mut data)?;
+        Some(member.map(|(header, footer)| (header, data, footer)))
+    }
+}
+
+impl<T: BufRead> Iterator for Members<T> {
+    type Item = Result<(MemberHeader, Vec<u8>), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let member = self.next_with_footer()?;
+        Some(member.map(|(header, data, _footer)| (header, data)))
+    }
+}
+
+/// Decode the single member starting at the reader's position and leave the
+/// reader right after its footer, for containers framing members themselves.
+pub fn decode_member<R: BufRead>(reader: &mut R) -> Result<DecodedMember, DecodeError> {
+    match GzipReader::new(reader).members().next_with_footer() {
+        Some(member) => member,
+        None => Err(anyhow!("no gzip member at the current position").into()),
     }
 }
 
@@ -272,3 +825,406 @@ impl<T: BufRead> MemberReader<T> {
         ))
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_with_fixed_crc16() -> Vec<u8> {
+        let mut data = vec![ID1, ID2, CM_DEFLATE, 0b1010, 0, 0, 0, 0, 0, 3];
+        let fixed_crc = CRC_CFG.checksum(&data);
+        data.extend_from_slice(b"file.txt\0");
+        data.extend_from_slice(&(fixed_crc as u16).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn compression_method_display() {
+        assert_eq!(CompressionMethod::Deflate.to_string(), "deflate");
+        assert_eq!(CompressionMethod::Unknown(9).to_string(), "unknown (9)");
+    }
+
+    #[test]
+    fn lenient_header_crc() -> Result<()> {
+        let data = header_with_fixed_crc16();
+
+        let mut reader = GzipReader::new(data.as_slice());
+        assert!(reader.read_header().unwrap().is_err());
+
+        let options = DecodeOptions {
+            lenient_header_crc: true,
+            ..DecodeOptions::default()
+        };
+        let mut reader = GzipReader::with_options(data.as_slice(), options);
+        let (header, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(header.name.as_deref(), Some("file.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn require_header_crc() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0, 0, 0, 0, 0, 3];
+
+        let mut reader = GzipReader::new(&data[..]);
+        assert!(reader.read_header().unwrap().is_ok());
+
+        let options = DecodeOptions {
+            require_header_crc: true,
+            ..DecodeOptions::default()
+        };
+        let mut reader = GzipReader::with_options(&data[..], options);
+        let err = reader.read_header().unwrap().unwrap_err();
+        assert!(err.to_string().contains("header crc16 is required"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_size_modulo_2_32() {
+        let footer = make_footer(b"");
+        let size = 5u64 << 30;
+        let footer = MemberFooter {
+            data_size: size as u32,
+            ..footer
+        };
+        footer.verify(size, footer.data_crc32).unwrap();
+        assert!(footer.verify(size + 1, footer.data_crc32).is_err());
+        assert!(footer
+            .verify(size as u32 as u64 + 1, footer.data_crc32)
+            .is_err());
+    }
+
+    #[test]
+    fn read_footer_from_byte_chunks() -> Result<()> {
+        let data = [0x78, 0x56, 0x34, 0x12, 0x10, 0x32, 0x54, 0x76, 0xff];
+        let reader = std::io::BufReader::with_capacity(1, &data[..]);
+
+        let (footer, mut reader) = MemberReader::new(reader).read_footer()?;
+        assert_eq!(footer.data_crc32, 0x12345678);
+        assert_eq!(footer.data_size, 0x76543210);
+        assert_eq!(reader.reader().read_u8()?, 0xff);
+
+        Ok(())
+    }
+
+    #[test]
+    fn minimal_header() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0x78, 0x56, 0x34, 0x12, 2, 3, 0xff];
+        let mut reader = GzipReader::new(&data[..]);
+        let (fast, flags) = reader.read_header().unwrap()?;
+        assert_eq!(flags.0, 0);
+        assert_eq!(reader.reader().read_u8()?, 0xff);
+
+        // Same header with FTEXT set goes through the general path.
+        let mut data = data;
+        data[3] = 1;
+        let mut reader = GzipReader::new(&data[..]);
+        let (mut general, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(reader.reader().read_u8()?, 0xff);
+        assert!(general.is_text);
+        general.is_text = false;
+
+        assert_eq!(fast, general);
+        assert_eq!(fast.modification_time, 0x12345678);
+        assert_eq!(fast.extra_flags, 2);
+        assert_eq!(fast.os, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn multiline_comment() -> Result<()> {
+        let comment = "first line\nsecond line\r\n\tindented\n\n";
+        let mut data = vec![ID1, ID2, CM_DEFLATE, 0b1_0000, 0, 0, 0, 0, 0, 3];
+        data.extend_from_slice(comment.as_bytes());
+        data.extend_from_slice(&[0, 0xff]);
+
+        let mut reader = GzipReader::new(data.as_slice());
+        let (header, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(header.comment(), Some(comment));
+        assert_eq!(reader.reader(), &[0xff]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn latin1_name_and_comment() -> Result<()> {
+        let mut data = vec![ID1, ID2, CM_DEFLATE, 0b1_1010, 0, 0, 0, 0, 0, 3];
+        data.extend_from_slice(b"caf\xe9.txt\0\xa9 \xff\0");
+        let crc = CRC_CFG.checksum(&data);
+        data.extend_from_slice(&(crc as u16).to_le_bytes());
+
+        let mut reader = GzipReader::new(data.as_slice());
+        let (header, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(header.name.as_deref(), Some("café.txt"));
+        assert_eq!(header.comment(), Some("© ÿ"));
+        assert_eq!(header.encoded_len(), data.len());
+        let mut encoded = vec![];
+        header.write_to(&mut encoded)?;
+        assert_eq!(encoded, data);
+
+        let header = MemberHeader {
+            name: Some("ключ".to_owned()),
+            ..header
+        };
+        let mut encoded = vec![];
+        header.write_to(&mut encoded)?;
+        assert_eq!(encoded.len(), header.encoded_len());
+        let (decoded, _flags) = GzipReader::new(encoded.as_slice()).read_header().unwrap()?;
+        assert_eq!(decoded.name.as_deref(), Some("????"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_name_and_comment() -> Result<()> {
+        let mut data = vec![ID1, ID2, CM_DEFLATE, 0b1_1010, 0, 0, 0, 0, 0, 3, 0, 0];
+        let crc = CRC_CFG.checksum(&data);
+        data.extend_from_slice(&(crc as u16).to_le_bytes());
+
+        let mut reader = GzipReader::new(data.as_slice());
+        let (header, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(header.name.as_deref(), Some(""));
+        assert_eq!(header.comment(), Some(""));
+
+        let options = DecodeOptions {
+            empty_name_as_none: true,
+            ..DecodeOptions::default()
+        };
+        let mut reader = GzipReader::with_options(data.as_slice(), options);
+        let (header, flags) = reader.read_header().unwrap()?;
+        assert_eq!(header.name, None);
+        assert_eq!(header.comment(), None);
+        assert!(flags.has_name());
+        assert!(reader.reader().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn os_name() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0, 0, 0, 0, 0, 3];
+        let (mut header, _flags) = GzipReader::new(&data[..]).read_header().unwrap()?;
+        let options = DecodeOptions::default();
+        assert_eq!(header.os_name(&options), Some("Unix"));
+        header.os = OS_UNKNOWN;
+        assert_eq!(header.os_name(&options), Some("unknown"));
+        header.os = 0x42;
+        assert_eq!(header.os_name(&options), None);
+
+        let options = DecodeOptions {
+            os_name_fn: Some(|os| (os == 0x42).then_some("VendorOS")),
+            ..DecodeOptions::default()
+        };
+        assert_eq!(header.os_name(&options), Some("VendorOS"));
+        header.os = 3;
+        assert_eq!(header.os_name(&options), Some("Unix"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn operating_system() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0, 0, 0, 0, 0, 11];
+        let (mut header, _flags) = GzipReader::new(&data[..]).read_header().unwrap()?;
+        assert_eq!(header.os(), OperatingSystem::Ntfs);
+        assert_eq!(header.os().to_string(), "NTFS");
+        header.os = OS_UNKNOWN;
+        assert_eq!(header.os(), OperatingSystem::Unknown(OS_UNKNOWN));
+        assert_eq!(header.os().to_string(), "unknown");
+        header.os = 0x42;
+        assert_eq!(header.os().to_string(), "unknown (66)");
+
+        for os in 0..=u8::MAX {
+            assert_eq!(u8::from(OperatingSystem::from(os)), os);
+            if let Some(&name) = OS_NAMES.get(os as usize) {
+                assert_eq!(OperatingSystem::from(os).to_string(), name);
+            }
+            header.os = os;
+            assert_eq!(
+                header.os_name(&DecodeOptions::default()),
+                header.os().name()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_extra() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0b100, 0, 0, 0, 0, 0, 3, 0, 0, 0xff];
+        let mut reader = GzipReader::new(&data[..]);
+        let (header, flags) = reader.read_header().unwrap()?;
+        assert!(flags.has_extra());
+        assert_eq!(header.extra, Some(vec![]));
+        assert!(header.extra_subfields()?.is_empty());
+        assert_eq!(reader.reader(), &[0xff]);
+
+        let mut written = vec![];
+        header.write_to(&mut written)?;
+        assert_eq!(written, data[..12]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn safe_name() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0, 0, 0, 0, 0, 3];
+        let (mut header, _flags) = GzipReader::new(&data[..]).read_header().unwrap()?;
+        assert_eq!(header.safe_name(), None);
+
+        for (name, safe) in [
+            ("file.txt", Some("file.txt")),
+            ("../../etc/passwd", Some("passwd")),
+            ("dir/sub/file.txt", Some("file.txt")),
+            ("/abs/path.gz", Some("path.gz")),
+            ("..\\..\\boot.ini", Some("boot.ini")),
+            ("..", None),
+            ("dir/..", None),
+            ("dir/", None),
+            (".", None),
+            ("", None),
+        ] {
+            header.name = Some(name.to_owned());
+            assert_eq!(header.safe_name().as_deref(), safe, "{}", name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn header_crc_over_bytes_read() -> Result<()> {
+        let mut data = vec![ID1, ID2, CM_DEFLATE, 0b1_1110, 0, 0, 0, 0, 0, 3, 2, 0, 1, 2];
+        data.extend_from_slice(b"name\0comment\0");
+        let crc = CRC_CFG.checksum(&data) as u16;
+        data.extend_from_slice(&crc.to_le_bytes());
+        let (header, _flags) = GzipReader::new(data.as_slice()).read_header().unwrap()?;
+        let mut written = vec![];
+        header.write_to(&mut written)?;
+        assert_eq!(written, data);
+
+        let name_pos = 14;
+        data[name_pos] = b'N';
+        let err = GzipReader::new(data.as_slice())
+            .read_header()
+            .unwrap()
+            .unwrap_err();
+        let DecodeError::HeaderCrcMismatch(mismatch) = err else {
+            panic!("expected HeaderCrcMismatch, got {}", err);
+        };
+        assert_eq!(mismatch.stored, crc);
+        let computed = CRC_CFG.checksum(&data[..data.len() - 2]) as u16;
+        assert_eq!(mismatch.computed, computed);
+        assert_eq!(mismatch.header_len, data.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reserved_flags() {
+        for bit in 5..8 {
+            let data = [ID1, ID2, CM_DEFLATE, 1 << bit, 0, 0, 0, 0, 0, 3];
+            let err = GzipReader::new(&data[..])
+                .read_header()
+                .unwrap()
+                .unwrap_err();
+            assert!(err.to_string().contains("reserved flag bits"), "{:#}", err);
+        }
+    }
+
+    #[test]
+    fn extra_subfields() -> Result<()> {
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0, 0, 0, 0, 0, 3];
+        let (mut header, _flags) = GzipReader::new(&data[..]).read_header().unwrap()?;
+        assert!(header.extra_subfields()?.is_empty());
+
+        header.extra = Some(vec![b'A', b'p', 2, 0, 7, 8, b'R', b'O', 0, 0]);
+        assert_eq!(
+            header.extra_subfields()?,
+            [(*b"Ap", &[7, 8][..]), (*b"RO", &[][..])]
+        );
+
+        header.extra = Some(vec![b'A', b'p', 3, 0, 7, 8]);
+        assert!(header.extra_subfields().is_err());
+        header.extra = Some(vec![b'A', b'p', 0]);
+        assert!(header.extra_subfields().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn boxed_dyn_reader() -> Result<()> {
+        let data = vec![ID1, ID2, CM_DEFLATE, 0, 0, 0, 0, 0, 0, 3, 0xff];
+        let reader = Box::new(std::io::Cursor::new(data)) as Box<dyn BufRead>;
+        let mut reader = GzipReader::new(reader);
+        let (header, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(header.os, 3);
+        assert_eq!(reader.reader().read_u8()?, 0xff);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mtime() -> Result<()> {
+        // 2001-09-09T01:46:40Z
+        let data = [ID1, ID2, CM_DEFLATE, 0, 0x00, 0xca, 0x9a, 0x3b, 0, 3];
+        let (mut header, _flags) = GzipReader::new(&data[..]).read_header().unwrap()?;
+        assert_eq!(header.modification_time, 1_000_000_000);
+        assert_eq!(
+            header.mtime(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_000_000_000))
+        );
+
+        header.modification_time = 0;
+        assert_eq!(header.mtime(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn header_write_to() -> Result<()> {
+        let header = MemberHeader {
+            compression_method: CompressionMethod::Deflate,
+            modification_time: 0x12345678,
+            extra: Some(vec![1, 2, 3]),
+            name: Some("file.txt".to_string()),
+            comment: Some("comment".to_string()),
+            extra_flags: 2,
+            os: 3,
+            has_crc: true,
+            is_text: true,
+        };
+        let mut data = vec![];
+        header.write_to(&mut data)?;
+        assert_eq!(data.len(), header.encoded_len());
+
+        let mut reader = GzipReader::new(data.as_slice());
+        let (parsed, _flags) = reader.read_header().unwrap()?;
+        assert_eq!(parsed, header);
+        assert!(reader.reader().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn member_flags_bits() {
+        for bits in [0, 0b1, 0b1_1010, 0b1_1111] {
+            assert_eq!(MemberFlags::from_bits(bits).bits(), bits);
+        }
+
+        let flags = MemberFlags::from_bits(0b1_0110);
+        assert!(!flags.is_text());
+        assert!(flags.has_crc());
+        assert!(flags.has_extra());
+        assert!(!flags.has_name());
+        assert!(flags.has_comment());
+
+        let mut flags = MemberFlags::from_bits(0);
+        flags.set_has_name(true);
+        flags.set_is_text(true);
+        assert_eq!(flags.bits(), 0b1001);
+    }
+}
@@ -1,14 +1,14 @@
 #![forbid(unsafe_code)]
 
-use std::{collections::HashMap, convert::TryFrom, io::BufRead};
+use std::{convert::TryFrom, io::BufRead, marker::PhantomData, mem::size_of};
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 
 use crate::bit_reader::{BitReader, BitSequence};
 
 ////////////////////////////////////////////////////////////////////////////////
 
-pub fn get_fixed_tree() -> Result<(HuffmanCoding<LitLenToken>, HuffmanCoding<DistanceToken>)> {
+pub fn get_fixed_tree(table_budget: Option<usize>) -> BlockCodes {
     let mut lengths = vec![];
     for _i in 0..=143 {
         lengths.push(8);
@@ -23,14 +23,24 @@ pub fn get_fixed_tree() -> Result<(HuffmanCoding<LitLenToken>, HuffmanCoding<Dis
         lengths.push(8);
     }
     let dists = vec![5; 32];
-    let litlen_tree = HuffmanCoding::<LitLenToken>::from_lengths(lengths.as_slice())?;
-    let dist_tree = HuffmanCoding::<DistanceToken>::from_lengths(dists.as_slice())?;
-    Ok((litlen_tree, dist_tree))
+    BlockCodes::new(&lengths, &dists, table_budget)
 }
 
 pub fn decode_litlen_distance_trees<T: BufRead>(
     bit_reader: &mut BitReader<T>,
-) -> Result<(HuffmanCoding<LitLenToken>, HuffmanCoding<DistanceToken>)> {
+    table_budget: Option<usize>,
+) -> Result<BlockCodes> {
+    let (lengths, hlit) = read_code_lengths(bit_reader, table_budget)?;
+    build_litlen_distance_trees(&lengths[..hlit], &lengths[hlit..], table_budget)
+}
+
+/// Read the dynamic block header up to and including the code lengths,
+/// returning them along with HLIT. The first HLIT lengths are for the
+/// literal/length code, the rest for the distance code.
+pub fn read_code_lengths<T: BufRead>(
+    bit_reader: &mut BitReader<T>,
+    table_budget: Option<usize>,
+) -> Result<(Vec<u8>, usize)> {
     let hlit = bit_reader.read_bits(5)?.bits() as usize + 257;
     let hdist = bit_reader.read_bits(5)?.bits() as usize + 1;
     let hclen = bit_reader.read_bits(4)?.bits() as usize + 4;
@@ -43,8 +53,11 @@ pub fn decode_litlen_distance_trees<T: BufRead>(
     for i in 0usize..hclen {
         bl_tree[lengths_map[i]] = bit_reader.read_bits(3)?.bits() as u8;
     }
-    let mapper = HuffmanCoding::<TreeCodeToken>::from_lengths(&bl_tree)?;
-    let mut tokens = Vec::<u8>::new();
+    let layout = CodeLayout::new(&bl_tree, table_budget);
+    let mut storage = [0; CODE_LENGTH_STORAGE];
+    layout.fill::<TreeCodeToken>(&bl_tree, &mut storage);
+    let mapper = layout.view::<TreeCodeToken>(&storage);
+    let mut tokens = Vec::<u8>::with_capacity(hlit + hdist);
     while tokens.len() < hlit + hdist {
         let symbol = mapper.read_symbol(bit_reader)?;
         match symbol {
@@ -60,34 +73,27 @@ pub fn decode_litlen_distance_trees<T: BufRead>(
             }
         };
     }
-    let litlen_tree = HuffmanCoding::<LitLenToken>::from_lengths(&tokens[..hlit])?;
-
-    let potential_dist_tree = &tokens[hlit..];
-    let mut count_one_length = 0usize;
-    let mut count_positive_length = 0usize;
+    Ok((tokens, hlit))
+}
 
-    for &item in potential_dist_tree {
-        match item {
-            0 => {}
-            1 => {
-                count_one_length += 1;
-            }
-            _ => {
-                count_positive_length += 1;
-            }
+pub fn build_litlen_distance_trees(
+    litlen_lengths: &[u8],
+    potential_dist_tree: &[u8],
+    table_budget: Option<usize>,
+) -> Result<BlockCodes> {
+    for symbol in [30, 31] {
+        if potential_dist_tree.get(symbol).is_some_and(|&len| len != 0) {
+            bail!("invalid distance code {}", symbol);
         }
     }
 
-    let mut lengths_bad = vec![0u8, 31];
-    lengths_bad.push(1u8);
-
-    let lengths = match (count_one_length, count_positive_length) {
-        (1, 0) => &lengths_bad,
-        _ => potential_dist_tree,
-    };
-
-    let dist_tree = HuffmanCoding::<DistanceToken>::from_lengths(lengths)?;
-    Ok((litlen_tree, dist_tree))
+    // A single distance code of length 1 leaves the other one-bit code
+    // unused, which is handled like any incomplete code.
+    Ok(BlockCodes::new(
+        litlen_lengths,
+        potential_dist_tree,
+        table_budget,
+    ))
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -119,6 +125,29 @@ impl TryFrom<HuffmanCodeWord> for TreeCodeToken {
     }
 }
 
+impl TableToken for TreeCodeToken {
+    fn pack(self) -> u32 {
+        match self {
+            TreeCodeToken::Length(value) => (value as u32) << 2,
+            TreeCodeToken::CopyPrev => 1,
+            TreeCodeToken::RepeatZero { base, extra_bits } => {
+                2 | (extra_bits as u32) << 2 | (base as u32) << 6
+            }
+        }
+    }
+
+    fn unpack(packed: u32) -> Self {
+        match packed & 3 {
+            0 => TreeCodeToken::Length((packed >> 2) as u8),
+            1 => TreeCodeToken::CopyPrev,
+            _ => TreeCodeToken::RepeatZero {
+                base: (packed >> 6) as u16,
+                extra_bits: (packed >> 2 & 0xf) as u8,
+            },
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Copy, Debug)]
@@ -256,6 +285,29 @@ impl TryFrom<HuffmanCodeWord> for LitLenToken {
     }
 }
 
+impl TableToken for LitLenToken {
+    fn pack(self) -> u32 {
+        match self {
+            LitLenToken::Literal(byte) => (byte as u32) << 2,
+            LitLenToken::EndOfBlock => 1,
+            LitLenToken::Length { base, extra_bits } => {
+                2 | (extra_bits as u32) << 2 | (base as u32) << 6
+            }
+        }
+    }
+
+    fn unpack(packed: u32) -> Self {
+        match packed & 3 {
+            0 => LitLenToken::Literal((packed >> 2) as u8),
+            1 => LitLenToken::EndOfBlock,
+            _ => LitLenToken::Length {
+                base: (packed >> 6) as u16,
+                extra_bits: (packed >> 2 & 0xf) as u8,
+            },
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Copy, Debug)]
@@ -394,74 +446,320 @@ impl TryFrom<HuffmanCodeWord> for DistanceToken {
     }
 }
 
+impl TableToken for DistanceToken {
+    fn pack(self) -> u32 {
+        self.extra_bits as u32 | (self.base as u32) << 4
+    }
+
+    fn unpack(packed: u32) -> Self {
+        DistanceToken {
+            base: (packed >> 4) as u16,
+            extra_bits: (packed & 0xf) as u8,
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 const MAX_BITS: usize = 15;
 
+/// Bits looked up at once. Longer codes take the canonical walk once the
+/// lookup misses, which keeps a table at 512 entries whatever the longest
+/// code; every fixed code fits.
+const TABLE_BITS: u8 = 9;
+
+/// The code length code has 19 symbols with lengths of up to 7 bits.
+const CODE_LENGTH_STORAGE: usize = (1 << 7) + 19;
+
 pub struct HuffmanCodeWord(pub u16);
 
-pub struct HuffmanCoding<T> {
-    map: HashMap<BitSequence, T>,
+/// A token that packs into 28 bits, so that codes for different tokens can
+/// share one allocation.
+pub trait TableToken: Copy + TryFrom<HuffmanCodeWord, Error = anyhow::Error> {
+    fn pack(self) -> u32;
+    fn unpack(packed: u32) -> Self;
+}
+
+/// A packed token above the length of its code. Lengths are never 0, so 0
+/// marks no entry.
+type TableEntry = u32;
+
+fn table_entry<T: TableToken>(token: T, len: u8) -> TableEntry {
+    token.pack() << 4 | len as u32
+}
+
+/// How one code is laid out in the storage it may share with other codes:
+/// its lookup table, then its symbols.
+#[derive(Clone, Copy, Debug)]
+pub struct CodeLayout {
+    /// Number of codes of each length.
+    counts: [u16; MAX_BITS + 1],
+    table_bits: u8,
+    table_len: usize,
+    symbol_count: usize,
+}
+
+impl CodeLayout {
+    /// Lay out the code for `code_lengths`, with a lookup table only if it
+    /// takes at most `table_budget` bytes. Without it decoding is slower, but
+    /// the output is the same.
+    pub fn new(code_lengths: &[u8], table_budget: Option<usize>) -> Self {
+        let mut counts = [0; MAX_BITS + 1];
+        for &len in code_lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let symbol_count = counts.iter().map(|&count| count as usize).sum::<usize>();
+        let table_bits = code_lengths
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .min(TABLE_BITS);
+        let table_len = match table_budget {
+            _ if symbol_count == 0 => 0,
+            Some(budget) if (1 << table_bits) * size_of::<TableEntry>() > budget => 0,
+            _ => 1 << table_bits,
+        };
+        Self {
+            counts,
+            table_bits,
+            table_len,
+            symbol_count,
+        }
+    }
+
+    /// Number of entries the code takes.
+    pub fn storage_len(&self) -> usize {
+        self.table_len + self.symbol_count
+    }
+
+    /// Build the code for `code_lengths` into the first `storage_len`
+    /// entries of `storage`.
+    pub fn fill<T: TableToken>(&self, code_lengths: &[u8], storage: &mut [TableEntry]) {
+        let (table, symbols) = storage[..self.storage_len()].split_at_mut(self.table_len);
+        let mut next = [0; MAX_BITS + 1];
+        for len in 2..=MAX_BITS {
+            next[len] = next[len - 1] + self.counts[len - 1] as usize;
+        }
+        for (symbol, code) in canonical_codes(code_lengths) {
+            let len = code.len();
+            // Codes may be assigned to symbols that are never valid, like
+            // literal/length symbols 286 and 287. Those keep no entry.
+            let Ok(token) = T::try_from(HuffmanCodeWord(symbol)) else {
+                next[len as usize] += 1;
+                continue;
+            };
+            let entry = table_entry(token, len);
+            symbols[next[len as usize]] = entry;
+            next[len as usize] += 1;
+            if len > self.table_bits {
+                continue;
+            }
+            // Codes are packed most significant bit first, input comes in
+            // least significant bit first.
+            let first = code.bits().reverse_bits() >> (16 - len as u32);
+            for index in (first as usize..table.len()).step_by(1 << len) {
+                table[index] = entry;
+            }
+        }
+    }
+
+    /// The code built by `fill` into `storage`.
+    pub fn view<'a, T>(&'a self, storage: &'a [TableEntry]) -> HuffmanCoding<'a, T> {
+        let (table, symbols) = storage[..self.storage_len()].split_at(self.table_len);
+        HuffmanCoding {
+            counts: &self.counts,
+            symbols,
+            table,
+            table_bits: self.table_bits,
+            token: PhantomData,
+        }
+    }
+}
+
+/// The literal/length and distance codes of a block, built into a single
+/// allocation.
+pub struct BlockCodes {
+    storage: Box<[TableEntry]>,
+    litlen: CodeLayout,
+    dist: CodeLayout,
+}
+
+impl BlockCodes {
+    pub fn new(litlen_lengths: &[u8], dist_lengths: &[u8], table_budget: Option<usize>) -> Self {
+        let litlen = CodeLayout::new(litlen_lengths, table_budget);
+        let dist = CodeLayout::new(dist_lengths, table_budget);
+        let mut storage = vec![0; litlen.storage_len() + dist.storage_len()].into_boxed_slice();
+        let (litlen_storage, dist_storage) = storage.split_at_mut(litlen.storage_len());
+        litlen.fill::<LitLenToken>(litlen_lengths, litlen_storage);
+        dist.fill::<DistanceToken>(dist_lengths, dist_storage);
+        Self {
+            storage,
+            litlen,
+            dist,
+        }
+    }
+
+    pub fn litlen(&self) -> HuffmanCoding<'_, LitLenToken> {
+        self.litlen.view(&self.storage)
+    }
+
+    pub fn dist(&self) -> HuffmanCoding<'_, DistanceToken> {
+        self.dist.view(&self.storage[self.litlen.storage_len()..])
+    }
 }
 
-impl<T> HuffmanCoding<T>
-where
-    T: Copy + TryFrom<HuffmanCodeWord, Error = anyhow::Error>,
-{
-    // pub fn new(map: HashMap<BitSequence, T>) -> Self {
-    //     Self { map }
-    // }
+/// A code decoded from storage laid out by a `CodeLayout`.
+pub struct HuffmanCoding<'a, T> {
+    counts: &'a [u16; MAX_BITS + 1],
+    /// Entries of the symbols in canonical order: by code length, then by
+    /// value. 0 for symbols without a token.
+    symbols: &'a [TableEntry],
+    /// Indexed by the next `table_bits` input bits in stream order, so a
+    /// symbol with a code of at most `table_bits` bits takes a single
+    /// lookup. Empty when it would exceed the table budget; symbols are
+    /// found by walking `counts` then.
+    table: &'a [TableEntry],
+    table_bits: u8,
+    token: PhantomData<T>,
+}
 
-    #[allow(unused)]
+impl<T: TableToken> HuffmanCoding<'_, T> {
+    /// Number of decodable symbols in the code.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.symbols.iter().filter(|&&entry| entry != 0).count()
+    }
+
+    #[cfg(test)]
     pub fn decode_symbol(&self, seq: BitSequence) -> Option<T> {
-        self.map.get(&seq).copied()
+        // Codes are packed most significant bit first, the window holds the
+        // input least significant bit first.
+        let window = seq.bits().reverse_bits() >> (16 - seq.len() as u32);
+        match self.decode_from_window(window, seq.len()) {
+            Ok((symbol, len)) if len == seq.len() => Some(symbol),
+            _ => None,
+        }
     }
 
     pub fn read_symbol<U: BufRead>(&self, bit_reader: &mut BitReader<U>) -> Result<T> {
-        let mut current = BitSequence::new(0, 0);
-        for _ in 1usize..=MAX_BITS {
-            let bit = bit_reader.read_bits(1)?;
-            current = bit.concat(current);
-            match self.map.get(&current) {
-                Some(val_ref) => return Ok(*val_ref),
-                None => continue,
+        // Nothing can match, so don't eat bits that belong to what follows.
+        ensure!(!self.symbols.is_empty(), "empty huffman code");
+        if !self.table.is_empty() {
+            let window = bit_reader.peek_bits(self.table_bits)?;
+            // No entry means a code longer than the table, or an unassigned
+            // one; the walk below tells which.
+            if window.len() == self.table_bits {
+                let entry = self.table[window.bits() as usize];
+                if entry != 0 {
+                    bit_reader.read_bits((entry & 0xf) as u8)?;
+                    return Ok(T::unpack(entry >> 4));
+                }
+            }
+        }
+        let window = bit_reader.peek_bits(MAX_BITS as u8)?;
+        match self.decode_from_window(window.bits(), window.len()) {
+            Ok((symbol, len)) => {
+                bit_reader.read_bits(len)?;
+                return Ok(symbol);
+            }
+            Err(err) if window.len() as usize == MAX_BITS => return Err(err),
+            // The window was cut short by the end of the buffered input, go
+            // bit by bit so that refills and EOF are handled by the reader.
+            Err(_) => {}
+        }
+        let mut window = 0;
+        for len in 1..=MAX_BITS as u8 {
+            window |= bit_reader.read_bits(1)?.bits() << (len - 1);
+            if let Ok((symbol, _len)) = self.decode_from_window(window, len) {
+                return Ok(symbol);
             }
         }
         Err(anyhow!("undefined symbol"))
     }
 
-    pub fn from_lengths(code_lengths: &[u8]) -> Result<Self> {
-        // println!("getting lengths count");
-        let mut bl_count = vec![0; MAX_BITS + 1];
-        for code_length in code_lengths {
-            bl_count[*code_length as usize] += 1;
-        }
-        // println!("getting next codes");
-        bl_count[0] = 0;
-        let mut code = 0;
-        let mut next_code = vec![0; MAX_BITS + 1];
-        for bits in 1usize..=MAX_BITS {
-            code = (code + bl_count[bits - 1]) << 1;
-            next_code[bits] = code;
-        }
-        // println!("getting tree");
-        let mut map = HashMap::<BitSequence, T>::new();
-        for code in 0u16..code_lengths.len() as u16 {
-            let len = code_lengths[code as usize] as usize;
-            if len != 0 {
-                // println!("inserting {:#b} {} ({})", next_code[len], code, len);
-                map.insert(
-                    BitSequence::new(next_code[len], len as u8),
-                    match T::try_from(HuffmanCodeWord(code)) {
-                        Ok(val) => val,
-                        _ => continue,
-                    },
-                );
-                next_code[len] += 1;
+    /// Decode the symbol at the start of `window`, whose lowest `valid_bits`
+    /// bits hold the upcoming input in stream order. Returns the symbol and
+    /// the number of bits its code takes; nothing is consumed from a reader.
+    pub fn decode_from_window(&self, window: u16, valid_bits: u8) -> Result<(T, u8)> {
+        // Canonical codes of one length are consecutive, starting at `first`,
+        // and their symbols are consecutive in `symbols` from `index`.
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for len in 1..=valid_bits.min(MAX_BITS as u8) {
+            code |= (window as usize >> (len - 1)) & 1;
+            let count = self.counts[len as usize] as usize;
+            if code < first + count {
+                return match self.symbols[index + code - first] {
+                    0 => Err(anyhow!("undefined symbol")),
+                    entry => Ok((T::unpack(entry >> 4), len)),
+                };
             }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(anyhow!("undefined symbol"))
+    }
+
+    /// Whether symbols are decoded through the lookup table.
+    #[cfg(test)]
+    pub fn has_table(&self) -> bool {
+        !self.table.is_empty()
+    }
+}
+
+/// The `(symbol, code)` pairs RFC 1951 3.2.2 assigns for `code_lengths`,
+/// skipping symbols of length 0.
+pub fn canonical_codes(code_lengths: &[u8]) -> impl Iterator<Item = (u16, BitSequence)> + '_ {
+    let mut bl_count = [0; MAX_BITS + 1];
+    for code_length in code_lengths {
+        bl_count[*code_length as usize] += 1;
+    }
+    bl_count[0] = 0;
+    let mut code = 0;
+    let mut next_code = [0; MAX_BITS + 1];
+    for bits in 1usize..=MAX_BITS {
+        code = (code + bl_count[bits - 1]) << 1;
+        next_code[bits] = code;
+    }
+    code_lengths
+        .iter()
+        .enumerate()
+        .filter(|(_, &len)| len != 0)
+        .map(move |(symbol, &len)| {
+            let code = BitSequence::new(next_code[len as usize], len);
+            next_code[len as usize] += 1;
+            (symbol as u16, code)
+        })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The code lengths and resulting codes of one dynamic block, for comparing
+/// against another decoder when tracking down a bad decode.
+#[cfg(feature = "dump-trees")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeDump {
+    /// HLIT literal/length code lengths.
+    pub litlen_lengths: Vec<u8>,
+    /// HDIST distance code lengths.
+    pub dist_lengths: Vec<u8>,
+    /// `(symbol, code)` for every literal/length symbol in use.
+    pub litlen_codes: Vec<(u16, BitSequence)>,
+    /// `(symbol, code)` for every distance symbol in use.
+    pub dist_codes: Vec<(u16, BitSequence)>,
+}
+
+#[cfg(feature = "dump-trees")]
+impl TreeDump {
+    pub fn new(litlen_lengths: Vec<u8>, dist_lengths: Vec<u8>) -> Self {
+        Self {
+            litlen_codes: canonical_codes(&litlen_lengths).collect(),
+            dist_codes: canonical_codes(&dist_lengths).collect(),
+            litlen_lengths,
+            dist_lengths,
         }
-        // println!("got tree");
-        Ok(Self { map })
     }
 }
 
@@ -482,9 +780,28 @@ mod tests {
         }
     }
 
+    impl TableToken for Value {
+        fn pack(self) -> u32 {
+            self.0 as u32
+        }
+
+        fn unpack(packed: u32) -> Self {
+            Self(packed as u16)
+        }
+    }
+
+    /// Build the code for `lengths` into storage of its own.
+    fn build(lengths: &[u8], budget: Option<usize>) -> (CodeLayout, Vec<TableEntry>) {
+        let layout = CodeLayout::new(lengths, budget);
+        let mut storage = vec![0; layout.storage_len()];
+        layout.fill::<Value>(lengths, &mut storage);
+        (layout, storage)
+    }
+
     #[test]
     fn from_lengths() -> Result<()> {
-        let code = HuffmanCoding::<Value>::from_lengths(&[2, 3, 4, 3, 3, 4, 2])?;
+        let (layout, storage) = build(&[2, 3, 4, 3, 3, 4, 2], None);
+        let code = layout.view::<Value>(&storage);
 
         assert_eq!(
             code.decode_symbol(BitSequence::new(0b00, 2)),
@@ -522,28 +839,135 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn fixed_tree() -> Result<()> {
+        let codes = get_fixed_tree(None);
+        let (litlen_tree, dist_tree) = (codes.litlen(), codes.dist());
+        // Symbols 286-287 and 30-31 get codes but never occur in valid data,
+        // so only 286 and 30 of the 288 and 32 codes decode to a token.
+        assert_eq!(litlen_tree.len(), 286);
+        assert_eq!(dist_tree.len(), 30);
+
+        assert!(matches!(
+            litlen_tree.decode_symbol(BitSequence::new(0b0000000, 7)),
+            Some(LitLenToken::EndOfBlock)
+        ));
+        assert!(matches!(
+            litlen_tree.decode_symbol(BitSequence::new(0b11000101, 8)),
+            Some(LitLenToken::Length {
+                base: 258,
+                extra_bits: 0
+            })
+        ));
+        assert!(litlen_tree
+            .decode_symbol(BitSequence::new(0b11000110, 8))
+            .is_none());
+        assert!(dist_tree
+            .decode_symbol(BitSequence::new(0b11110, 5))
+            .is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn read_symbol() -> Result<()> {
-        let code = HuffmanCoding::<Value>::from_lengths(&[2, 3, 4, 3, 3, 4, 2])?;
-        let mut data: &[u8] = &[0b10111001, 0b11001010, 0b11101101];
-        let mut reader = BitReader::new(&mut data);
+        // The table takes 16 entries; a budget of 0 leaves only the walk.
+        for (budget, has_table) in [(None, true), (Some(1 << 10), true), (Some(0), false)] {
+            let (layout, storage) = build(&[2, 3, 4, 3, 3, 4, 2], budget);
+            let code = layout.view::<Value>(&storage);
+            assert_eq!(code.has_table(), has_table);
+            let mut data: &[u8] = &[0b10111001, 0b11001010, 0b11101101];
+            let mut reader = BitReader::new(&mut data);
+
+            assert_eq!(code.read_symbol(&mut reader)?, Value(1));
+            assert_eq!(code.read_symbol(&mut reader)?, Value(2));
+            assert_eq!(code.read_symbol(&mut reader)?, Value(3));
+            assert_eq!(code.read_symbol(&mut reader)?, Value(6));
+            assert_eq!(code.read_symbol(&mut reader)?, Value(0));
+            assert_eq!(code.read_symbol(&mut reader)?, Value(2));
+            assert_eq!(code.read_symbol(&mut reader)?, Value(4));
+            assert!(code.read_symbol(&mut reader).is_err());
+        }
 
+        Ok(())
+    }
+
+    #[test]
+    fn read_symbol_from_incomplete_table() -> Result<()> {
+        // Codes 0, 10 and 110; 111 is unassigned.
+        let (layout, storage) = build(&[1, 2, 3], None);
+        let code = layout.view::<Value>(&storage);
+        assert!(code.has_table());
+        let mut data: &[u8] = &[0b1100_1101, 0xff];
+        let mut reader = BitReader::new(&mut data);
         assert_eq!(code.read_symbol(&mut reader)?, Value(1));
         assert_eq!(code.read_symbol(&mut reader)?, Value(2));
-        assert_eq!(code.read_symbol(&mut reader)?, Value(3));
-        assert_eq!(code.read_symbol(&mut reader)?, Value(6));
         assert_eq!(code.read_symbol(&mut reader)?, Value(0));
-        assert_eq!(code.read_symbol(&mut reader)?, Value(2));
-        assert_eq!(code.read_symbol(&mut reader)?, Value(4));
         assert!(code.read_symbol(&mut reader).is_err());
 
         Ok(())
     }
 
+    #[test]
+    fn read_symbol_longer_than_table() -> Result<()> {
+        // Code k is k ones and a zero, the last two are 14 and 15 ones.
+        let lengths: Vec<u8> = (1..=15).chain([15]).collect();
+        let (layout, storage) = build(&lengths, None);
+        let code = layout.view::<Value>(&storage);
+        assert!(code.has_table());
+        // Symbols 15, 0 and 13: 15 ones, a zero, 13 ones and a zero.
+        let mut data: &[u8] = &[0xff, 0x7f, 0xff, 0x1f];
+        let mut reader = BitReader::new(&mut data);
+        assert_eq!(code.read_symbol(&mut reader)?, Value(15));
+        assert_eq!(code.read_symbol(&mut reader)?, Value(0));
+        assert_eq!(code.read_symbol(&mut reader)?, Value(13));
+        assert_eq!(
+            code.decode_symbol(BitSequence::new(0x7ffe, 15)),
+            Some(Value(14))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_from_window() -> Result<()> {
+        let (layout, storage) = build(&[2, 3, 4, 3, 3, 4, 2], None);
+        let code = layout.view::<Value>(&storage);
+
+        assert_eq!(code.decode_from_window(0b00, 2)?, (Value(0), 2));
+        assert_eq!(code.decode_from_window(0b10, 2)?, (Value(6), 2));
+        assert_eq!(code.decode_from_window(0b001, 3)?, (Value(1), 3));
+        assert_eq!(code.decode_from_window(0b0111, 4)?, (Value(2), 4));
+        assert_eq!(code.decode_from_window(0b1111, 15)?, (Value(5), 4));
+        // Bits beyond the code, valid or not, are left alone.
+        assert_eq!(code.decode_from_window(0b1111_1001, 8)?, (Value(1), 3));
+        assert_eq!(code.decode_from_window(0xff01, 3)?, (Value(1), 3));
+
+        assert!(code.decode_from_window(0b111, 3).is_err());
+        assert!(code.decode_from_window(0b1, 1).is_err());
+        assert!(code.decode_from_window(0, 0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_symbol_from_empty_code() -> Result<()> {
+        let (layout, storage) = build(&[0, 0, 0], None);
+        let code = layout.view::<Value>(&storage);
+        let mut data: &[u8] = &[0b10111001];
+        let mut reader = BitReader::new(&mut data);
+
+        assert!(code.read_symbol(&mut reader).is_err());
+        assert_eq!(reader.read_bits(8)?, BitSequence::new(0b10111001, 8));
+
+        Ok(())
+    }
+
     #[test]
     fn from_lengths_with_zeros() -> Result<()> {
         let lengths = [3, 4, 5, 5, 0, 0, 6, 6, 4, 0, 6, 0, 7];
-        let code = HuffmanCoding::<Value>::from_lengths(&lengths)?;
+        let (layout, storage) = build(&lengths, None);
+        let code = layout.view::<Value>(&storage);
         let mut data: &[u8] = &[
             0b00100000, 0b00100001, 0b00010101, 0b10010101, 0b00110101, 0b00011101,
         ];
@@ -568,7 +992,8 @@ mod tests {
         let lengths = [
             9, 10, 10, 8, 8, 8, 5, 6, 4, 5, 4, 5, 4, 5, 4, 4, 5, 4, 4, 5, 4, 5, 4, 5, 5, 5, 4, 6, 6,
         ];
-        let code = HuffmanCoding::<Value>::from_lengths(&lengths)?;
+        let (layout, storage) = build(&lengths, None);
+        let code = layout.view::<Value>(&storage);
         let mut data: &[u8] = &[
             0b11111000, 0b10111100, 0b01010001, 0b11111111, 0b00110101, 0b11111001, 0b11011111,
             0b11100001, 0b01110111, 0b10011111, 0b10111111, 0b00110100, 0b10111010, 0b11111111,
@@ -1,106 +1,664 @@
 #![forbid(unsafe_code)]
 
-use std::io::{BufRead, Write};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
 
-use anyhow::{bail, ensure, Result};
-use byteorder::{LittleEndian, ReadBytesExt};
+use anyhow::{anyhow, bail, ensure, Result};
+use byteorder::{ByteOrder, LittleEndian};
 use gzip::MemberReader;
-use tracking_writer::TrackingWriter;
+use tracking_writer::{TrackingWriter, CRC_CFG};
 
 use crate::{
     // bit_reader::reverse_bits,
-    bit_reader::BitReader,
     deflate::DeflateReader,
-    gzip::{CompressionMethod, GzipReader},
     huffman_coding::{decode_litlen_distance_trees, get_fixed_tree, LitLenToken},
 };
 
 mod bit_reader;
+mod canonical;
+mod decoder;
 mod deflate;
+mod error;
+mod format;
 mod gzip;
 mod huffman_coding;
+mod options;
+pub mod prelude;
 mod tracking_writer;
+mod warning;
+mod zlib;
 
-pub fn decompress<R: BufRead, W: Write>(input: R, mut output: W) -> Result<()> {
-    let mut gzip_reader = GzipReader::new(input);
+pub use bit_reader::{BitReader, BitSequence};
+pub use canonical::{canonicalize, canonicalize_with_options};
+pub use decoder::{DecodeEvent, DecodeStats, GzDecoder};
+pub use deflate::CompressionType;
+pub use error::{DecodeError, HeaderCrcMismatch, OutputLimitExceeded};
+pub use format::{detect_format, peek_zlib_header, Format, Peeked, ZlibHeader};
+pub use gzip::{
+    decode_member, make_footer, CompressionMethod, GzipReader, MemberFlags, MemberFooter,
+    MemberHeader, Members, OperatingSystem,
+};
+#[cfg(feature = "dump-trees")]
+pub use huffman_coding::TreeDump;
+pub use options::{CanonicalizeOptions, ChecksumErrorPolicy, DecodeOptions, TextOptions};
+pub use warning::Warning;
+pub use zlib::{decompress_zlib, Adler32};
+
+const ITER_CHUNK_SIZE: usize = 1 << 16;
+const READ_BUFFER_SIZE: usize = 64 << 10;
+const ZLIB_BODY_HINT: &str =
+    "member body starts with a zlib header, the input may be zlib rather than gzip";
+
+pub fn decompress<R: BufRead, W: Write>(input: R, output: W) -> Result<(), DecodeError> {
+    decompress_with_options(input, output, &DecodeOptions::strict())
+}
+
+/// Same as `decompress`, but over trait objects so that callers with many
+/// reader and writer types share a single instantiation of the decoder.
+pub fn decompress_dyn(input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), DecodeError> {
+    decompress(input, output)
+}
+
+/// Same as `decompress`, but with an `io::Result` for use with I/O adapters.
+/// Underlying I/O errors are passed through, truncated input becomes
+/// `UnexpectedEof` and corrupt data `InvalidData`.
+pub fn decompress_io<R: BufRead, W: Write>(input: R, output: W) -> std::io::Result<()> {
+    Ok(decompress(input, output)?)
+}
+
+/// Same as `decompress`, for sources that only implement `Read`, such as a
+/// `Chain` of readers.
+pub fn decompress_read<R: Read, W: Write>(input: R, output: W) -> Result<(), DecodeError> {
+    decompress_buffered(input, output, READ_BUFFER_SIZE)
+}
+
+/// Same as `decompress_read`, reading `input` through a buffer of `buf_size`
+/// bytes. A large buffer pays off for sources where each read is expensive.
+pub fn decompress_buffered<R: Read, W: Write>(
+    input: R,
+    output: W,
+    buf_size: usize,
+) -> Result<(), DecodeError> {
+    decompress(BufReader::with_capacity(buf_size, input), output)
+}
+
+pub fn decompress_to_vec<R: BufRead>(input: R) -> Result<Vec<u8>, DecodeError> {
+    let mut output = vec![];
+    decompress(input, &mut output)?;
+    Ok(output)
+}
+
+/// Decompress into `out`, e.g. a memory map sized from ISIZE, without any
+/// output allocation. Returns the number of bytes written, or an error if the
+/// output doesn't fit.
+pub fn decompress_into_slice<R: BufRead>(input: R, out: &mut [u8]) -> Result<usize, DecodeError> {
+    let capacity = out.len();
+    let mut rest = out;
+    match decompress(input, &mut rest) {
+        Ok(()) => Ok(capacity - rest.len()),
+        Err(DecodeError::Io(err)) if err.kind() == io::ErrorKind::WriteZero => {
+            Err(DecodeError::OutputTooSmall { capacity })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub fn decompress_to_string<R: BufRead>(input: R) -> Result<String, DecodeError> {
+    let mut output = vec![];
+    decompress(input, &mut output)?;
+    Ok(String::from_utf8(output)?)
+}
+
+pub fn decompress_to_string_with_options<R: BufRead>(
+    input: R,
+    text_options: &TextOptions,
+) -> Result<String, DecodeError> {
+    let mut output = vec![];
+    decompress(input, &mut output)?;
+    Ok(String::from_utf8(text_options.apply(output))?)
+}
+
+pub fn decompress_to_string_lossy<R: BufRead>(input: R) -> Result<String, DecodeError> {
+    let mut output = vec![];
+    decompress(input, &mut output)?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Decode as much of `input` as possible, returning the output produced up to
+/// the first error along with that error. Meant for recovering what's left
+/// of damaged archives, so literals are not batched and none get lost.
+pub fn decompress_salvage<R: BufRead>(input: R) -> (Vec<u8>, Option<DecodeError>) {
+    let options = DecodeOptions {
+        output_buffer_size: 1,
+        ..DecodeOptions::strict()
+    };
+    let mut output = vec![];
+    let err = decompress_with_options(input, &mut output, &options).err();
+    (output, err)
+}
+
+/// Decode `input`, discarding the output, and return the trees of all its
+/// dynamic blocks.
+#[cfg(feature = "dump-trees")]
+pub fn dump_trees<R: BufRead>(input: R) -> Result<Vec<TreeDump>, DecodeError> {
+    let mut decoder = GzDecoder::new(input);
+    let mut buf = vec![0u8; ITER_CHUNK_SIZE];
+    while decoder.decode_into_buffer(&mut buf)? > 0 {}
+    Ok(decoder.tree_dumps().to_vec())
+}
+
+/// Decompress gzip, zlib or raw DEFLATE input, picking the format by peeking
+/// at its first bytes.
+pub fn decompress_auto<R: BufRead, W: Write>(input: R, output: W) -> Result<(), DecodeError> {
+    let (format, input) = detect_format(input)?;
+    match format {
+        Format::Gzip => decompress(input, output),
+        Format::Zlib => decompress_zlib(input, output),
+        Format::Raw => inflate(input, output),
+    }
+}
+
+/// Decompress a raw DEFLATE stream, without any gzip wrapper, up to its
+/// final block. There is no trailer, so nothing is verified besides the
+/// stream itself.
+pub fn inflate<R: BufRead, W: Write>(input: R, output: W) -> Result<(), DecodeError> {
+    let mut writer = TrackingWriter::new(output);
+    let mut deflate_reader = DeflateReader::new(BitReader::new(input));
+    inflate_blocks(
+        &mut deflate_reader,
+        &mut writer,
+        &DecodeOptions::default(),
+        0,
+        0,
+    )?;
+    Ok(())
+}
+
+/// Lazily decompress `input`, yielding it split on `\n` with the newlines
+/// removed. A last line without a trailing newline is yielded too. A line
+/// that isn't valid UTF-8 is an `Err` item; iteration stops after the first
+/// decoding error.
+pub fn decompress_lines<R: BufRead>(input: R) -> impl Iterator<Item = Result<String, DecodeError>> {
+    let mut lines = BufReader::new(GzDecoder::new(input)).split(b'\n');
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        Some(match lines.next()? {
+            Ok(line) => String::from_utf8(line).map_err(DecodeError::from),
+            Err(err) => {
+                failed = true;
+                Err(err.into())
+            }
+        })
+    })
+}
+
+/// Lazily decompress `input`, yielding the output in chunks of up to
+/// `ITER_CHUNK_SIZE` bytes. Iteration stops after the first error.
+pub fn decompress_iter<R: BufRead>(input: R) -> impl Iterator<Item = Result<Vec<u8>, DecodeError>> {
+    let mut decoder = GzDecoder::new(input);
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let mut chunk = vec![0u8; ITER_CHUNK_SIZE];
+        match decoder.decode_into_buffer(&mut chunk) {
+            Ok(0) => None,
+            Ok(size) => {
+                chunk.truncate(size);
+                Some(Ok(chunk))
+            }
+            Err(err) => {
+                failed = true;
+                Some(Err(err))
+            }
+        }
+    })
+}
+
+pub fn decompress_with_options<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    options: &DecodeOptions,
+) -> Result<(), DecodeError> {
+    decompress_with_warnings(input, output, options).map(|_warnings| ())
+}
+
+/// Same as `decompress`, but fails with `OutputLimitExceeded` instead of
+/// writing more than `max_bytes` in total.
+pub fn decompress_limited<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    max_bytes: u64,
+) -> Result<(), DecodeError> {
+    let options = DecodeOptions {
+        max_output_bytes: Some(max_bytes),
+        ..DecodeOptions::default()
+    };
+    decompress_with_options(input, output, &options)
+}
+
+/// Same as `decompress`, but returns the number of decompressed bytes across
+/// all members. Not to be confused with `decompress_counting`, which counts
+/// the input.
+pub fn decompress_counted<R: BufRead, W: Write>(input: R, output: W) -> Result<u64, DecodeError> {
+    let mut output = Counting::new(output);
+    decompress(input, &mut output)?;
+    Ok(output.count)
+}
+
+/// Passes reads or writes through while counting the bytes consumed or
+/// accepted. Written bytes also go into a CRC32 if one was asked for.
+struct Counting<T> {
+    inner: T,
+    count: u64,
+    digest: Option<crc::Digest<'static, u32>>,
+}
+
+impl<T> Counting<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            count: 0,
+            digest: None,
+        }
+    }
+
+    fn with_crc(inner: T) -> Self {
+        Self {
+            digest: Some(CRC_CFG.digest()),
+            ..Self::new(inner)
+        }
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let size = self.inner.write(buf)?;
+        self.count += size as u64;
+        if let Some(digest) = &mut self.digest {
+            digest.update(&buf[..size]);
+        }
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: BufRead> Read for Counting<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.count += size as u64;
+        Ok(size)
+    }
+}
+
+impl<T: BufRead> BufRead for Counting<T> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.count += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+/// Same as `decompress`, but a `HeaderCrcMismatch` error gets the raw bytes
+/// of the failing header attached, re-read by seeking back to its start.
+pub fn decompress_seekable<R: BufRead + Seek, W: Write>(
+    mut input: R,
+    output: W,
+) -> Result<(), DecodeError> {
+    let start = input.stream_position()?;
+    let mut counting = Counting::new(&mut input);
+    let err = match decompress(&mut counting, output) {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+    let consumed = counting.count;
+    let DecodeError::HeaderCrcMismatch(mismatch) = err else {
+        return Err(err);
+    };
+    let mut raw_header = vec![0; mismatch.header_len];
+    input.seek(SeekFrom::Start(
+        start + consumed - mismatch.header_len as u64,
+    ))?;
+    input.read_exact(&mut raw_header)?;
+    Err(DecodeError::HeaderCrcMismatch(HeaderCrcMismatch {
+        raw_header: Some(raw_header),
+        ..mismatch
+    }))
+}
+
+/// Whether another gzip member follows the first one, as in bgzip or pigz
+/// output. The first member is decoded and verified, but its data is
+/// discarded. The input is seeked back to where it started.
+pub fn is_multistream<R: BufRead + Seek>(mut input: R) -> Result<bool, DecodeError> {
+    let start = input.stream_position()?;
+    let result = first_member_then_magic(&mut input);
+    input.seek(SeekFrom::Start(start))?;
+    Ok(result?)
+}
+
+fn first_member_then_magic<R: BufRead>(input: &mut R) -> Result<bool> {
+    match GzipReader::new(&mut *input).members().next_into(io::sink()) {
+        Some(member) => member?,
+        None => bail!(DecodeError::BadMagic),
+    };
+    let mut magic = vec![];
+    input.take(2).read_to_end(&mut magic)?;
+    Ok(magic == format::GZIP_MAGIC)
+}
+
+/// Same as `decompress_with_options`, but returns how many input bytes were
+/// consumed. With `stop_after_last_member` set the count ends right after the
+/// last footer, even if trailing bytes follow.
+pub fn decompress_counting<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    options: &DecodeOptions,
+) -> Result<u64, DecodeError> {
+    let mut input = Counting::new(input);
+    decompress_with_options(&mut input, output, options)?;
+    Ok(input.count)
+}
+
+/// Same as `decompress`, but returns one CRC32 over the output of all
+/// members together. Each member is still checked against its own footer.
+pub fn decompress_whole_crc<R: BufRead, W: Write>(input: R, output: W) -> Result<u32, DecodeError> {
+    let mut output = Counting::with_crc(output);
+    decompress(input, &mut output)?;
+    Ok(output.digest.map_or(0, |digest| digest.finalize()))
+}
+
+/// Same as `decompress_with_options`, but also returns the warnings raised
+/// while decoding.
+pub fn decompress_with_warnings<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    options: &DecodeOptions,
+) -> Result<Vec<Warning>, DecodeError> {
+    Ok(decode_members(input, output, options, |_header| {})?)
+}
+
+/// Same as `decompress`, but calls `on_header` with every member's header
+/// right after it is parsed, before the member's body is decoded.
+pub fn decompress_with<R: BufRead, W: Write, F: FnMut(&MemberHeader)>(
+    input: R,
+    output: W,
+    on_header: F,
+) -> Result<(), DecodeError> {
+    decode_members(input, output, &DecodeOptions::default(), on_header)?;
+    Ok(())
+}
+
+fn decode_members<R: BufRead, W: Write, F: FnMut(&MemberHeader)>(
+    input: R,
+    mut output: W,
+    options: &DecodeOptions,
+    mut on_header: F,
+) -> Result<Vec<Warning>> {
+    check_checksum_policy(options)?;
+    let mut gzip_reader = GzipReader::with_options(input, options.clone());
+    let mut compressed_bytes = 0u64;
+    let mut decompressed_bytes = 0u64;
+    let mut warnings = vec![];
+    let mut output = MemberOutput {
+        inner: &mut output,
+        held: options.buffer_until_verified.map(|_| vec![]),
+        max_held: options.buffer_until_verified.unwrap_or(0),
+    };
 
+    let mut member_index = 0;
     while let Some(member) = gzip_reader.read_header() {
-        let mut writer = TrackingWriter::new(&mut output);
+        let mut writer =
+            TrackingWriter::new(&mut output).with_max_distance(options.max_window_distance);
         let (header, _flags) = member?;
-        if let CompressionMethod::Unknown(_) = header.compression_method {
-            bail!("unsupported compression method")
+        on_header(&header);
+        warnings.append(&mut gzip_reader.take_warnings());
+        if let CompressionMethod::Unknown(method) = header.compression_method {
+            bail!(DecodeError::UnsupportedMethod(method))
         }
 
+        gzip_reader.check_body_present()?;
+        // Best effort: only the bytes already buffered are looked at.
+        let body_format = format::format_of(gzip_reader.reader().fill_buf()?);
         let bit_reader = BitReader::new(gzip_reader.reader());
         let mut deflate_reader = DeflateReader::new(bit_reader);
+        inflate_blocks(
+            &mut deflate_reader,
+            &mut writer,
+            options,
+            compressed_bytes,
+            decompressed_bytes,
+        )
+        .map_err(|err| match body_format {
+            Format::Zlib => err.context(ZLIB_BODY_HINT),
+            _ => err,
+        })?;
+        compressed_bytes += deflate_reader.bytes_read();
+        decompressed_bytes += writer.byte_count();
 
-        while let Some(block) = deflate_reader.next_block() {
-            let (cur_header, cur_reader) = block?;
-            if cur_header.compression_type == deflate::CompressionType::Uncompressed {
-                // println!("processing uncompressed block");
-                // cur_reader.read_bits(5)?;
-                let len = cur_reader
-                    .borrow_reader_from_boundary()
-                    .read_u16::<LittleEndian>()?;
-                let nlen = cur_reader
-                    .borrow_reader_from_boundary()
-                    .read_u16::<LittleEndian>()?;
-                ensure!(len == !nlen, "nlen check failed");
-                for _ in 0..len {
-                    writer.write_all(&[cur_reader.borrow_reader_from_boundary().read_u8()?])?;
-                }
-                // println!("processed uncompressed block");
-                continue;
-            }
-            let (litlen_tree, dist_tree) = match cur_header.compression_type {
-                deflate::CompressionType::FixedTree => {
-                    // println!("found fixed tree");
-                    get_fixed_tree()?
-                }
-                deflate::CompressionType::DynamicTree => {
-                    // println!("found dynamic tree");
-                    decode_litlen_distance_trees(cur_reader)?
-                }
-                _ => bail!("should not occur"),
-            };
-            // println!("processing block");
-            loop {
-                match litlen_tree.read_symbol(cur_reader)? {
-                    LitLenToken::Literal(byte) => {
-                        // println!("writing literal: {}", byte);
-                        writer.write_all(&[byte])?;
-                    }
-                    LitLenToken::Length { base, extra_bits } => {
-                        // println!("writing length: ({}, {})", base, extra_bits);
-                        // let len = base + reverse_bits(reader.read_bits(extra_bits)?.bits(), extra_bits);
-                        let len = base + cur_reader.read_bits(extra_bits)?.bits();
-                        // println!("  - got len: {}", len);
-                        let dist_token = dist_tree.read_symbol(cur_reader)?;
-                        // println!(
-                        //     "  - dist token: base={} extra_bits={}",
-                        //     dist_token.base, dist_token.extra_bits
-                        // );
-                        let dist =
-                            dist_token.base + cur_reader.read_bits(dist_token.extra_bits)?.bits();
-                        writer.write_previous(dist as usize, len as usize)?;
-                    }
-                    LitLenToken::EndOfBlock => {
-                        // println!("reached end of block");
-                        break;
-                    }
-                };
+        let member_reader = MemberReader::new(gzip_reader.reader());
+        let (footer, _reader) = member_reader.read_footer()?;
+
+        let keep = check_footer(options, &footer, &writer, member_index, &mut warnings)?;
+        match keep {
+            true => output.release()?,
+            false => output.discard(),
+        }
+        member_index += 1;
+    }
+    Ok(warnings)
+}
+
+fn check_checksum_policy(options: &DecodeOptions) -> Result<()> {
+    ensure!(
+        options.on_checksum_error != ChecksumErrorPolicy::Skip
+            || options.buffer_until_verified.is_some(),
+        "skipping members on checksum errors requires buffer_until_verified"
+    );
+    Ok(())
+}
+
+/// Check a member against its footer as `on_checksum_error` says. Returns
+/// whether the member's output is to be kept.
+fn check_footer<W: Write>(
+    options: &DecodeOptions,
+    footer: &MemberFooter,
+    writer: &TrackingWriter<W>,
+    member_index: usize,
+    warnings: &mut Vec<Warning>,
+) -> Result<bool> {
+    let verified = match options.verify_checksums {
+        true => footer.verify(writer.byte_count(), writer.crc32()),
+        false => Ok(()),
+    };
+    match (verified, options.on_checksum_error) {
+        (Ok(()), _) => Ok(true),
+        (Err(err), ChecksumErrorPolicy::Abort) => Err(err.into()),
+        (Err(_), ChecksumErrorPolicy::Skip) => Ok(false),
+        (Err(_), ChecksumErrorPolicy::KeepAndWarn) => {
+            warnings.push(Warning::ChecksumMismatch(member_index));
+            Ok(true)
+        }
+    }
+}
+
+/// Passes a member's output through, or holds it until `release` when
+/// `buffer_until_verified` is set.
+struct MemberOutput<W> {
+    inner: W,
+    held: Option<Vec<u8>>,
+    max_held: usize,
+}
+
+impl<W: Write> MemberOutput<W> {
+    fn release(&mut self) -> std::io::Result<()> {
+        if let Some(held) = &mut self.held {
+            self.inner.write_all(held)?;
+            held.clear();
+        }
+        Ok(())
+    }
+
+    fn discard(&mut self) {
+        if let Some(held) = &mut self.held {
+            held.clear();
+        }
+    }
+}
+
+impl<W: Write> Write for MemberOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.held {
+            Some(held) if held.len() + buf.len() > self.max_held => Err(std::io::Error::other(
+                "buffer_until_verified limit exceeded",
+            )),
+            Some(held) => {
+                held.extend_from_slice(buf);
+                Ok(buf.len())
             }
+            None => self.inner.write(buf),
         }
+    }
 
-        let member_reader = MemberReader::new(gzip_reader.reader());
-        let (footer, _reader) = member_reader.read_footer()?;
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-        if footer.data_size as usize != writer.byte_count() {
-            bail!("length check failed");
+fn check_block_count(options: &DecodeOptions, blocks: u64) -> Result<()> {
+    match options.max_blocks_per_member {
+        Some(max) if blocks > max => Err(anyhow!("block count limit exceeded")),
+        _ => Ok(()),
+    }
+}
+
+/// Fail if writing `more` bytes on top of the `produced` ones would break
+/// `max_output_bytes`.
+fn check_output_size(options: &DecodeOptions, produced: u64, more: usize) -> Result<()> {
+    match options.max_output_bytes {
+        Some(limit) if produced + more as u64 > limit => {
+            Err(DecodeError::OutputLimitExceeded(OutputLimitExceeded { limit, produced }).into())
         }
+        _ => Ok(()),
+    }
+}
 
-        if footer.data_crc32 != writer.crc32() {
-            bail!("crc32 check failed");
+/// Decode DEFLATE blocks into `writer` until the final block is processed.
+/// `consumed_before` and `produced_before` are the compressed and decompressed
+/// sizes of the previous members.
+fn inflate_blocks<R: BufRead, W: Write>(
+    deflate_reader: &mut DeflateReader<R>,
+    writer: &mut TrackingWriter<W>,
+    options: &DecodeOptions,
+    consumed_before: u64,
+    produced_before: u64,
+) -> Result<()> {
+    let check_compressed_size = |consumed: u64| match options.max_compressed_bytes {
+        Some(max) if consumed_before + consumed > max => {
+            Err(anyhow!("compressed size limit exceeded"))
+        }
+        _ => Ok(()),
+    };
+    ensure!(
+        options.output_buffer_size > 0,
+        "output buffer size must be nonzero"
+    );
+    let mut literals = Vec::with_capacity(options.output_buffer_size);
+    let mut blocks = 0u64;
+
+    while let Some(block) = deflate_reader.next_block() {
+        let (cur_header, cur_reader) = block?;
+        check_compressed_size(cur_reader.bytes_read())?;
+        blocks += 1;
+        check_block_count(options, blocks)?;
+        if cur_header.compression_type == deflate::CompressionType::Uncompressed {
+            // println!("processing uncompressed block");
+            let mut len_nlen = [0u8; 4];
+            cur_reader.read_aligned(&mut len_nlen)?;
+            let len = LittleEndian::read_u16(&len_nlen[..2]);
+            let nlen = LittleEndian::read_u16(&len_nlen[2..]);
+            ensure!(
+                len == !nlen || !options.verify_stored_nlen,
+                "nlen check failed"
+            );
+            check_compressed_size(cur_reader.bytes_read() + len as u64)?;
+            let mut buf = [0u8; 4096];
+            let mut left = len as usize;
+            while left > 0 {
+                let chunk = left.min(buf.len());
+                check_output_size(options, produced_before + writer.byte_count(), chunk)?;
+                cur_reader.read_aligned(&mut buf[..chunk])?;
+                writer.write_all(&buf[..chunk])?;
+                left -= chunk;
+            }
+            // println!("processed uncompressed block");
+            continue;
+        }
+        let codes = match cur_header.compression_type {
+            deflate::CompressionType::FixedTree => {
+                // println!("found fixed tree");
+                get_fixed_tree(options.huffman_table_budget)
+            }
+            deflate::CompressionType::DynamicTree => {
+                // println!("found dynamic tree");
+                decode_litlen_distance_trees(cur_reader, options.huffman_table_budget)?
+            }
+            other => bail!("unexpected block type: {}", other),
+        };
+        let (litlen_tree, dist_tree) = (codes.litlen(), codes.dist());
+        // println!("processing block");
+        loop {
+            check_compressed_size(cur_reader.bytes_read())?;
+            match litlen_tree.read_symbol(cur_reader)? {
+                LitLenToken::Literal(byte) => {
+                    // println!("writing literal: {}", byte);
+                    let produced = produced_before + writer.byte_count() + literals.len() as u64;
+                    if let Err(err) = check_output_size(options, produced, 1) {
+                        writer.write_all(&literals)?;
+                        return Err(err);
+                    }
+                    literals.push(byte);
+                    if literals.len() >= options.output_buffer_size {
+                        writer.write_all(&literals)?;
+                        literals.clear();
+                    }
+                }
+                LitLenToken::Length { base, extra_bits } => {
+                    // println!("writing length: ({}, {})", base, extra_bits);
+                    // let len = base + reverse_bits(reader.read_bits(extra_bits)?.bits(), extra_bits);
+                    let len = base + cur_reader.read_bits(extra_bits)?.bits();
+                    // println!("  - got len: {}", len);
+                    let dist_token = dist_tree.read_symbol(cur_reader)?;
+                    // println!(
+                    //     "  - dist token: base={} extra_bits={}",
+                    //     dist_token.base, dist_token.extra_bits
+                    // );
+                    let dist =
+                        dist_token.base + cur_reader.read_bits(dist_token.extra_bits)?.bits();
+                    writer.write_all(&literals)?;
+                    literals.clear();
+                    check_output_size(
+                        options,
+                        produced_before + writer.byte_count(),
+                        len as usize,
+                    )?;
+                    writer.write_previous(dist as usize, len as usize)?;
+                }
+                LitLenToken::EndOfBlock => {
+                    // println!("reached end of block");
+                    writer.write_all(&literals)?;
+                    literals.clear();
+                    break;
+                }
+            };
         }
     }
     Ok(())
@@ -0,0 +1,261 @@
+#![forbid(unsafe_code)]
+
+////////////////////////////////////////////////////////////////////////////////
+
+const SECURE_MAX_COMPRESSED_BYTES: u64 = 1 << 30;
+const SECURE_MAX_OUTPUT_BYTES: u64 = 4 << 30;
+const SECURE_MAX_BLOCKS_PER_MEMBER: u64 = 1 << 20;
+const SECURE_MAX_WINDOW_DISTANCE: usize = 32 << 10;
+const SECURE_HUFFMAN_TABLE_BUDGET: usize = 64 << 10;
+const DEFAULT_OUTPUT_BUFFER_SIZE: usize = 16 << 10;
+const FAST_OUTPUT_BUFFER_SIZE: usize = 64 << 10;
+
+/// What to do when a member's footer doesn't match its decoded data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChecksumErrorPolicy {
+    /// Fail the whole decode.
+    #[default]
+    Abort,
+    /// Drop the member's output and go on with the next member. Needs
+    /// `buffer_until_verified` so that the output can still be dropped.
+    Skip,
+    /// Emit the member's output anyway and record a
+    /// `Warning::ChecksumMismatch`.
+    KeepAndWarn,
+}
+
+#[derive(Clone, Debug)]
+pub struct DecodeOptions {
+    /// Accept a header whose FHCRC covers only the fixed 10-byte part, as
+    /// written by some historical encoders.
+    pub lenient_header_crc: bool,
+    /// Reject members that don't carry a header CRC (FHCRC flag unset).
+    pub require_header_crc: bool,
+    /// Upper bound on the compressed DEFLATE bytes consumed across all
+    /// members, guarding against endless input.
+    pub max_compressed_bytes: Option<u64>,
+    /// Upper bound on the DEFLATE blocks in a single member, guarding against
+    /// an endless run of tiny non-final blocks.
+    pub max_blocks_per_member: Option<u64>,
+    /// Upper bound on the decompressed bytes across all members, guarding
+    /// against decompression bombs. Checked before each write, so the output
+    /// never grows past it.
+    pub max_output_bytes: Option<u64>,
+    /// Check each member's CRC32 and length against its footer.
+    pub verify_checksums: bool,
+    /// Check that a stored block's NLEN is the complement of its LEN. Only
+    /// turn off for output of producers known to get NLEN wrong: a corrupt
+    /// LEN then goes unnoticed until the data after it fails to decode.
+    pub verify_stored_nlen: bool,
+    /// How many literal bytes are collected before they are handed to the
+    /// output in one write. Must be nonzero.
+    pub output_buffer_size: usize,
+    /// Measure the wall-clock time spent decoding, see `GzDecoder::last_stats`.
+    pub measure_time: bool,
+    /// Finish successfully once a member is followed by something other than
+    /// the gzip magic, leaving those trailing bytes unread in the input.
+    pub stop_after_last_member: bool,
+    /// Report an FNAME or FCOMMENT that holds just the terminating NUL as
+    /// `None`. Off by default, so `Some("")` tells "present but empty" apart
+    /// from "absent".
+    pub empty_name_as_none: bool,
+    /// Hold each member's output, up to this many bytes, and only pass it on
+    /// once the footer has been checked. Trades memory for never emitting
+    /// bytes that fail verification.
+    pub buffer_until_verified: Option<usize>,
+    /// Names OS bytes for `MemberHeader::os_name`, taking precedence over
+    /// the RFC 1952 list. Return `None` to fall back to it.
+    pub os_name_fn: Option<fn(u8) -> Option<&'static str>>,
+    /// Handling of members failing the footer checks, if those are enabled.
+    pub on_checksum_error: ChecksumErrorPolicy,
+    /// Reject back-references further than this many bytes, simulating a
+    /// decoder with a smaller window. Capped to the 32768 bytes DEFLATE
+    /// allows; `None` means the full window.
+    pub max_window_distance: Option<usize>,
+    /// Upper bound in bytes on the lookup table built for each Huffman code,
+    /// which takes at most 512 entries. A code whose table would be larger is
+    /// decoded by walking its code lengths instead: slower, with the same
+    /// output. `None` means no bound.
+    pub huffman_table_budget: Option<usize>,
+}
+
+impl DecodeOptions {
+    /// Full validation and no limits. This is what `decompress` uses.
+    pub fn strict() -> Self {
+        Self {
+            lenient_header_crc: false,
+            require_header_crc: false,
+            max_compressed_bytes: None,
+            max_blocks_per_member: None,
+            max_output_bytes: None,
+            verify_checksums: true,
+            verify_stored_nlen: true,
+            output_buffer_size: DEFAULT_OUTPUT_BUFFER_SIZE,
+            measure_time: false,
+            stop_after_last_member: false,
+            empty_name_as_none: false,
+            buffer_until_verified: None,
+            os_name_fn: None,
+            on_checksum_error: ChecksumErrorPolicy::Abort,
+            max_window_distance: None,
+            huffman_table_budget: None,
+        }
+    }
+
+    /// Tolerate known quirks of old encoders.
+    pub fn lenient() -> Self {
+        Self {
+            lenient_header_crc: true,
+            ..Self::strict()
+        }
+    }
+
+    /// Skip the footer and NLEN checks and write in larger batches, for
+    /// speed. Corrupt data may go unnoticed.
+    pub fn fast() -> Self {
+        Self {
+            verify_checksums: false,
+            verify_stored_nlen: false,
+            output_buffer_size: FAST_OUTPUT_BUFFER_SIZE,
+            ..Self::strict()
+        }
+    }
+
+    /// Strict validation with every resource limit set, for untrusted input.
+    /// The window limit is the full DEFLATE window, so no valid stream is
+    /// rejected for it.
+    pub fn secure() -> Self {
+        Self {
+            max_compressed_bytes: Some(SECURE_MAX_COMPRESSED_BYTES),
+            max_blocks_per_member: Some(SECURE_MAX_BLOCKS_PER_MEMBER),
+            max_output_bytes: Some(SECURE_MAX_OUTPUT_BYTES),
+            max_window_distance: Some(SECURE_MAX_WINDOW_DISTANCE),
+            huffman_table_budget: Some(SECURE_HUFFMAN_TABLE_BUDGET),
+            ..Self::strict()
+        }
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Header bytes `canonicalize` may normalize besides the ones it always
+/// clears. Both are kept by default.
+#[derive(Clone, Debug, Default)]
+pub struct CanonicalizeOptions {
+    /// Write the OS byte as 255 (unknown).
+    pub normalize_os: bool,
+    /// Write the XFL byte as 0.
+    pub normalize_extra_flags: bool,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// Post-processing applied to decoded text. Everything is off by default so
+/// the output stays byte-exact.
+#[derive(Clone, Debug, Default)]
+pub struct TextOptions {
+    /// Drop a leading UTF-8 byte order mark.
+    pub strip_bom: bool,
+    /// Turn CRLF line endings into LF.
+    pub normalize_newlines: bool,
+}
+
+impl TextOptions {
+    pub fn apply(&self, mut data: Vec<u8>) -> Vec<u8> {
+        if self.strip_bom && data.starts_with(UTF8_BOM) {
+            data.drain(..UTF8_BOM.len());
+        }
+        if !self.normalize_newlines {
+            return data;
+        }
+        let mut normalized = Vec::with_capacity(data.len());
+        for (i, &byte) in data.iter().enumerate() {
+            if byte == b'\r' && data.get(i + 1) == Some(&b'\n') {
+                continue;
+            }
+            normalized.push(byte);
+        }
+        normalized
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        let strict = DecodeOptions::strict();
+        assert!(!strict.lenient_header_crc);
+        assert!(!strict.require_header_crc);
+        assert!(strict.verify_checksums);
+        assert!(strict.verify_stored_nlen);
+        assert_eq!(strict.max_compressed_bytes, None);
+        assert_eq!(strict.max_blocks_per_member, None);
+        assert_eq!(strict.max_output_bytes, None);
+        assert_eq!(strict.output_buffer_size, DEFAULT_OUTPUT_BUFFER_SIZE);
+        assert!(!strict.stop_after_last_member);
+        assert!(!strict.empty_name_as_none);
+        assert_eq!(strict.buffer_until_verified, None);
+        assert!(strict.os_name_fn.is_none());
+        assert_eq!(strict.on_checksum_error, ChecksumErrorPolicy::Abort);
+        assert_eq!(strict.max_window_distance, None);
+        assert_eq!(strict.huffman_table_budget, None);
+
+        assert!(DecodeOptions::lenient().lenient_header_crc);
+        assert!(DecodeOptions::lenient().verify_checksums);
+
+        let fast = DecodeOptions::fast();
+        assert!(!fast.verify_checksums);
+        assert!(!fast.verify_stored_nlen);
+        assert_eq!(fast.output_buffer_size, FAST_OUTPUT_BUFFER_SIZE);
+        assert!(!fast.measure_time);
+
+        let secure = DecodeOptions::secure();
+        assert!(!secure.lenient_header_crc);
+        assert!(secure.verify_checksums);
+        assert!(secure.verify_stored_nlen);
+        assert_eq!(
+            secure.max_compressed_bytes,
+            Some(SECURE_MAX_COMPRESSED_BYTES)
+        );
+        assert_eq!(
+            secure.max_blocks_per_member,
+            Some(SECURE_MAX_BLOCKS_PER_MEMBER)
+        );
+        assert_eq!(secure.max_output_bytes, Some(SECURE_MAX_OUTPUT_BYTES));
+        assert_eq!(secure.max_window_distance, Some(SECURE_MAX_WINDOW_DISTANCE));
+        assert_eq!(
+            secure.huffman_table_budget,
+            Some(SECURE_HUFFMAN_TABLE_BUDGET)
+        );
+    }
+
+    #[test]
+    fn text_options() {
+        let data = b"\xef\xbb\xbfa\r\nb\rc\r\n".to_vec();
+        assert_eq!(TextOptions::default().apply(data.clone()), data);
+
+        let strip_bom = TextOptions {
+            strip_bom: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(strip_bom.apply(data.clone()), b"a\r\nb\rc\r\n");
+
+        let normalize_newlines = TextOptions {
+            normalize_newlines: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(normalize_newlines.apply(data), b"\xef\xbb\xbfa\nb\rc\n");
+    }
+}
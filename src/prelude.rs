@@ -0,0 +1,16 @@
+#![forbid(unsafe_code)]
+
+//! The commonly used part of the public API.
+//!
+//! ```
+//! use ripgzip::prelude::*;
+//!
+//! let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+//! let output = decompress_to_vec(&data[..]).unwrap();
+//! assert!(output.starts_with(b"[package]"));
+//!
+//! let err: DecodeError = decompress_to_vec(&b"not gzip"[..]).unwrap_err();
+//! assert!(matches!(err, DecodeError::BadMagic));
+//! ```
+
+pub use crate::{decompress, decompress_to_vec, DecodeError, DecodeOptions, GzDecoder};
@@ -1,22 +1,28 @@
 #![forbid(unsafe_code)]
 
-use std::collections::VecDeque;
-use std::io::{self, Write};
+use std::io::{self, IoSlice, Write};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{bail, Result};
 use crc::{Crc, Digest, CRC_32_ISO_HDLC};
 
+use crate::error::DecodeError;
+
 ////////////////////////////////////////////////////////////////////////////////
 
 const HISTORY_SIZE: usize = 32768;
 
-#[warn(dead_code)]
-pub const CRC_CFG: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+/// Shared by every digest: its lookup table is built at compile time and a
+/// `Digest` only borrows it, so starting a new digest, e.g. in `reset`, is
+/// just a few word writes.
+pub static CRC_CFG: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
 pub struct TrackingWriter<T> {
     inner: T,
-    buf: VecDeque<u8>,
-    bytes_counter: usize,
+    window: Box<[u8]>,
+    window_pos: usize,
+    window_filled: usize,
+    max_distance: usize,
+    bytes_counter: u64,
     crc_digest: Digest<'static, u32>,
 }
 
@@ -24,22 +30,28 @@ impl<T: Write> Write for TrackingWriter<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match self.inner.write(buf) {
             Ok(size) => {
-                for item in buf.iter().take(size) {
-                    if self.buf.len() >= HISTORY_SIZE {
-                        self.buf.pop_front();
-                    }
-                    self.buf.push_back(*item);
-                }
-                self.crc_digest.update(&buf[0..size]);
-                self.bytes_counter += size;
+                self.observe(&buf[..size]);
                 Ok(size)
             }
             err => err,
         }
     }
 
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let size = self.inner.write_vectored(bufs)?;
+        let mut left = size;
+        for buf in bufs {
+            if left == 0 {
+                break;
+            }
+            let accepted = left.min(buf.len());
+            self.observe(&buf[..accepted]);
+            left -= accepted;
+        }
+        Ok(size)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
-        self.buf.clear();
         self.inner.flush()
     }
 }
@@ -48,41 +60,123 @@ impl<T: Write> TrackingWriter<T> {
     pub fn new(inner: T) -> Self {
         Self {
             inner,
-            buf: VecDeque::<u8>::new(),
-            bytes_counter: 0usize,
+            window: vec![0u8; HISTORY_SIZE].into_boxed_slice(),
+            window_pos: 0,
+            window_filled: 0,
+            max_distance: HISTORY_SIZE,
+            bytes_counter: 0,
             crc_digest: CRC_CFG.digest(),
         }
     }
 
+    /// Make `write_previous` reject distances beyond `max_distance`, as a
+    /// decoder with a smaller window would. Values above the DEFLATE window
+    /// size are capped to it.
+    pub fn with_max_distance(mut self, max_distance: Option<usize>) -> Self {
+        self.max_distance = max_distance.map_or(HISTORY_SIZE, |max| max.min(HISTORY_SIZE));
+        self
+    }
+
     /// Write a sequence of `len` bytes written `dist` bytes ago.
+    ///
+    /// The bytes are copied inside the history window and handed to the inner
+    /// writer straight from there, so no temporary buffer is allocated. Long
+    /// matches are expanded at most a window's worth at a time.
     pub fn write_previous(&mut self, dist: usize, len: usize) -> Result<()> {
-        // println!(
-        //     "writing previous (dist = {}, len = {}, buffer_size = {})",
-        //     dist,
-        //     len,
-        //     self.buf.len()
-        // );
-        if dist > self.buf.len() {
-            bail!("bad dist");
-            // bail!("length check failed crc32 check failed unsupported compression method");
+        // Must be checked before any index arithmetic on the window.
+        if dist == 0 || dist > self.window_len() {
+            bail!(DecodeError::BadDistance {
+                distance: dist,
+                available: self.window_len(),
+            });
         }
-        let mut buf = vec![];
-        for idx in 0..len {
-            buf.push(self.buf[self.buf.len() - dist + (idx % dist)]);
+        if len == 0 {
+            return Ok(());
         }
-        match self.write(buf.as_slice()) {
-            Ok(size) if size < len => Err(anyhow!("buffer overflow")),
-            Ok(_) => Ok(()),
-            Err(err) => Err(anyhow::Error::new(err)),
+        let mut left = len;
+        while left > 0 {
+            let src = (self.window_pos + HISTORY_SIZE - dist) % HISTORY_SIZE;
+            let dst = self.window_pos;
+            let chunk = left.min(HISTORY_SIZE - src).min(HISTORY_SIZE - dst);
+            self.expand(src, dst, dist, chunk);
+
+            let size = self.inner.write(&self.window[dst..dst + chunk])?;
+            self.crc_digest.update(&self.window[dst..dst + size]);
+            self.advance(size);
+            self.bytes_counter += size as u64;
+            if size < chunk {
+                bail!("buffer overflow");
+            }
+            left -= chunk;
         }
+        Ok(())
     }
 
-    pub fn byte_count(&self) -> usize {
+    /// Number of bytes `write_previous` can currently reach back.
+    pub fn window_len(&self) -> usize {
+        self.window_filled.min(self.max_distance)
+    }
+
+    /// Bytes written since the last reset. A `u64` so that it doesn't wrap
+    /// on 32-bit targets.
+    pub fn byte_count(&self) -> u64 {
         self.bytes_counter
     }
 
-    pub fn crc32(self) -> u32 {
-        self.crc_digest.finalize()
+    pub fn crc32(&self) -> u32 {
+        self.crc_digest.clone().finalize()
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Forget the history, byte count and CRC, e.g. to start a new member.
+    pub fn reset(&mut self) {
+        self.window_pos = 0;
+        self.window_filled = 0;
+        self.bytes_counter = 0;
+        self.crc_digest = CRC_CFG.digest();
+    }
+
+    /// Copy `len` bytes from `src` to `dst` within the window. Overlapping
+    /// ranges (`len > dist`) repeat the pattern like LZ77 requires.
+    fn expand(&mut self, src: usize, dst: usize, dist: usize, len: usize) {
+        if dist == 1 {
+            // Run of a single byte, the most common match in practice.
+            let byte = self.window[src];
+            self.window[dst..dst + len].fill(byte);
+        } else if len <= dist {
+            self.window.copy_within(src..src + len, dst);
+        } else {
+            for i in 0..len {
+                self.window[dst + i] = self.window[src + i];
+            }
+        }
+    }
+
+    /// Account for `data` having been accepted by the inner writer.
+    fn observe(&mut self, data: &[u8]) {
+        self.remember(data);
+        self.crc_digest.update(data);
+        self.bytes_counter += data.len() as u64;
+    }
+
+    fn remember(&mut self, mut data: &[u8]) {
+        if data.len() > HISTORY_SIZE {
+            data = &data[data.len() - HISTORY_SIZE..];
+        }
+        while !data.is_empty() {
+            let chunk = data.len().min(HISTORY_SIZE - self.window_pos);
+            self.window[self.window_pos..self.window_pos + chunk].copy_from_slice(&data[..chunk]);
+            self.advance(chunk);
+            data = &data[chunk..];
+        }
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.window_pos = (self.window_pos + len) % HISTORY_SIZE;
+        self.window_filled = (self.window_filled + len).min(HISTORY_SIZE);
     }
 }
 
@@ -114,6 +208,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn write_vectored() -> Result<()> {
+        let parts: [&[u8]; 3] = [b"abc", b"", b"defgh"];
+        let slices = parts.map(IoSlice::new);
+
+        let mut single = TrackingWriter::new(io::sink());
+        single.write_all(&parts.concat())?;
+        let mut out = vec![];
+        let mut vectored = TrackingWriter::new(&mut out);
+        assert_eq!(vectored.write_vectored(&slices)?, 8);
+        assert_eq!(vectored.byte_count(), single.byte_count());
+        assert_eq!(vectored.crc32(), single.crc32());
+        vectored.write_previous(8, 8)?;
+        drop(vectored);
+        assert_eq!(out, b"abcdefghabcdefgh");
+
+        // Only the bytes the inner writer accepts are accounted for.
+        let mut buf: &mut [u8] = &mut [0u8; 5];
+        let mut partial = TrackingWriter::new(&mut buf);
+        let size = partial.write_vectored(&slices)?;
+        assert!(size <= 5);
+        let mut expected = TrackingWriter::new(io::sink());
+        expected.write_all(&parts.concat()[..size])?;
+        assert_eq!(partial.byte_count(), size as u64);
+        assert_eq!(partial.crc32(), expected.crc32());
+
+        Ok(())
+    }
+
     #[test]
     fn write_previous() -> Result<()> {
         let mut buf: &mut [u8] = &mut [0u8; 512];
@@ -138,4 +261,198 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn write_previous_wraps_window() -> Result<()> {
+        let mut out = vec![];
+        let mut expected = vec![];
+        let mut writer = TrackingWriter::new(&mut out);
+
+        for i in 0..HISTORY_SIZE + 1000 {
+            let byte = (i * 7 % 251) as u8;
+            writer.write_u8(byte)?;
+            expected.push(byte);
+        }
+        for &(dist, len) in &[
+            (3, 258),
+            (HISTORY_SIZE, 258),
+            (HISTORY_SIZE - 5, 100),
+            (1, 30),
+        ] {
+            writer.write_previous(dist, len)?;
+            for _ in 0..len {
+                expected.push(expected[expected.len() - dist]);
+            }
+        }
+        assert_eq!(writer.byte_count(), expected.len() as u64);
+        drop(writer);
+        assert_eq!(out, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_previous_rejects_dist_beyond_window() -> Result<()> {
+        let mut out = vec![];
+        let mut writer = TrackingWriter::new(&mut out);
+
+        writer.write_all(&[7; 100])?;
+        assert!(writer.write_previous(101, 1).is_err());
+        assert!(writer.write_previous(0, 1).is_err());
+        assert_eq!(writer.byte_count(), 100);
+
+        writer.write_all(&[7; HISTORY_SIZE])?;
+        assert!(writer.write_previous(HISTORY_SIZE + 1, 1).is_err());
+        assert_eq!(writer.byte_count(), HISTORY_SIZE as u64 + 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_previous_at_exact_window_size() -> Result<()> {
+        let data: Vec<u8> = (0..HISTORY_SIZE).map(|i| (i % 251) as u8).collect();
+        let mut out = vec![];
+        let mut writer = TrackingWriter::new(&mut out);
+
+        writer.write_all(&data)?;
+        assert!(writer.write_previous(HISTORY_SIZE + 1, 1).is_err());
+        writer.write_previous(HISTORY_SIZE, 1)?;
+        // The first byte is gone from the window now, the second is oldest.
+        writer.write_previous(HISTORY_SIZE, 1)?;
+        assert!(writer.write_previous(HISTORY_SIZE + 1, 1).is_err());
+        assert_eq!(writer.byte_count(), HISTORY_SIZE as u64 + 2);
+        drop(writer);
+        assert_eq!(out[HISTORY_SIZE..], data[..2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_previous_repeats_last_byte() -> Result<()> {
+        let mut out = vec![];
+        let mut writer = TrackingWriter::new(&mut out);
+
+        writer.write_all(b"ab")?;
+        writer.write_previous(1, 258)?;
+        assert_eq!(writer.byte_count(), 260);
+        drop(writer);
+        assert_eq!(&out[..2], b"ab");
+        assert_eq!(out[2..], [b'b'; 258]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn window_len() -> Result<()> {
+        let mut writer = TrackingWriter::new(io::sink());
+        assert_eq!(writer.window_len(), 0);
+
+        writer.write_all(&[1; 1000])?;
+        assert_eq!(writer.window_len(), 1000);
+        writer.write_previous(1000, 500)?;
+        assert_eq!(writer.window_len(), 1500);
+
+        writer.write_all(&[2; HISTORY_SIZE])?;
+        assert_eq!(writer.window_len(), HISTORY_SIZE);
+        writer.write_previous(HISTORY_SIZE, 258)?;
+        assert_eq!(writer.window_len(), HISTORY_SIZE);
+        assert!(writer.write_previous(writer.window_len() + 1, 1).is_err());
+
+        Ok(())
+    }
+
+    struct ChunkRecorder {
+        data: Vec<u8>,
+        writes: Vec<usize>,
+    }
+
+    impl Write for ChunkRecorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.extend_from_slice(buf);
+            self.writes.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_previous_max_length() -> Result<()> {
+        for dist in 1..=3 {
+            let mut recorder = ChunkRecorder {
+                data: vec![],
+                writes: vec![],
+            };
+            let mut writer = TrackingWriter::new(&mut recorder);
+            writer.write_all(b"xyz")?;
+            writer.write_previous(dist, 258)?;
+            assert_eq!(writer.byte_count(), 261);
+            drop(writer);
+
+            let mut expected = b"xyz".to_vec();
+            for _ in 0..258 {
+                expected.push(expected[expected.len() - dist]);
+            }
+            assert_eq!(recorder.data, expected);
+            assert_eq!(recorder.writes, [3, 258]);
+        }
+
+        let mut recorder = ChunkRecorder {
+            data: vec![],
+            writes: vec![],
+        };
+        let mut writer = TrackingWriter::new(&mut recorder);
+        writer.write_all(&[1; HISTORY_SIZE - 100])?;
+        writer.write_previous(2, 258)?;
+        drop(writer);
+        assert_eq!(recorder.writes, [HISTORY_SIZE - 100, 100, 2, 156]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn byte_count_past_u32() -> Result<()> {
+        // Start just below 4 GiB rather than writing that much.
+        let mut writer = TrackingWriter::new(io::sink());
+        writer.bytes_counter = u32::MAX as u64 - 1;
+        writer.write_all(b"abc")?;
+        writer.write_previous(1, 10)?;
+        assert_eq!(writer.byte_count(), u32::MAX as u64 + 12);
+        Ok(())
+    }
+
+    #[test]
+    fn write_previous_zero_len() -> Result<()> {
+        let mut writer = TrackingWriter::new(vec![]);
+        writer.write_all(b"abc")?;
+        let crc32 = writer.crc32();
+        writer.write_previous(3, 0)?;
+        assert_eq!(writer.byte_count(), 3);
+        assert_eq!(writer.window_len(), 3);
+        assert_eq!(writer.crc32(), crc32);
+        assert_eq!(writer.inner_mut(), b"abc");
+
+        // The distance is still checked.
+        assert!(writer.write_previous(0, 0).is_err());
+        assert!(writer.write_previous(4, 0).is_err());
+        writer.write_previous(1, 2)?;
+        assert_eq!(writer.inner_mut(), b"abccc");
+        Ok(())
+    }
+
+    #[test]
+    fn flush_keeps_history() -> Result<()> {
+        let mut writer = TrackingWriter::new(vec![]);
+        writer.write_all(b"abc")?;
+        let crc32 = writer.crc32();
+        writer.flush()?;
+        assert_eq!(writer.window_len(), 3);
+        assert_eq!(writer.byte_count(), 3);
+        assert_eq!(writer.crc32(), crc32);
+        writer.write_previous(3, 6)?;
+        assert_eq!(writer.inner_mut(), b"abcabcabc");
+        Ok(())
+    }
 }
@@ -0,0 +1,15 @@
+#![forbid(unsafe_code)]
+
+use std::time::SystemTime;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Something unusual about the input that doesn't prevent decoding it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// The member's MTIME lies after the current time.
+    FutureModificationTime(SystemTime),
+    /// The footer of the member with this index, counting from 0, didn't
+    /// match its data, which was kept per `ChecksumErrorPolicy::KeepAndWarn`.
+    ChecksumMismatch(usize),
+}
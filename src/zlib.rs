@@ -0,0 +1,146 @@
+#![forbid(unsafe_code)]
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::{bail, ensure, Result};
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+    bit_reader::BitReader,
+    deflate::DeflateReader,
+    error::DecodeError,
+    format::{ZLIB_CM_DEFLATE, ZLIB_FDICT, ZLIB_MAX_CINFO},
+    inflate_blocks,
+    options::DecodeOptions,
+    tracking_writer::TrackingWriter,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const ADLER_MOD: u32 = 65521;
+// Most bytes that can be summed before `b` may overflow a u32.
+const ADLER_NMAX: usize = 5552;
+
+/// Adler-32 checksum as used by the zlib trailer.
+#[derive(Clone, Copy, Debug)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(ADLER_NMAX) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER_MOD;
+            self.b %= ADLER_MOD;
+        }
+    }
+
+    pub fn finalize(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Passes writes through while computing the Adler-32 of the accepted bytes.
+struct AdlerWriter<W> {
+    inner: W,
+    adler: Adler32,
+}
+
+impl<W: Write> Write for AdlerWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = self.inner.write(buf)?;
+        self.adler.update(&buf[..size]);
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Decompress a zlib stream (RFC 1950): the CMF/FLG header, a DEFLATE body
+/// and the big-endian Adler-32 of the decompressed data. Streams needing a
+/// preset dictionary are rejected.
+pub fn decompress_zlib<R: BufRead, W: Write>(input: R, output: W) -> Result<(), DecodeError> {
+    Ok(decode_zlib(input, output)?)
+}
+
+fn decode_zlib<R: BufRead, W: Write>(mut input: R, output: W) -> Result<()> {
+    let cmf = input.read_u8()?;
+    let flg = input.read_u8()?;
+    ensure!(
+        (cmf as u16 * 256 + flg as u16).is_multiple_of(31),
+        "zlib header check failed"
+    );
+    if cmf & 0x0f != ZLIB_CM_DEFLATE {
+        bail!("unsupported zlib compression method: {}", cmf & 0x0f);
+    }
+    ensure!(cmf >> 4 <= ZLIB_MAX_CINFO, "bad zlib window size");
+    ensure!(
+        flg & ZLIB_FDICT == 0,
+        "zlib preset dictionaries are not supported"
+    );
+
+    let mut output = AdlerWriter {
+        inner: output,
+        adler: Adler32::new(),
+    };
+    let mut writer = TrackingWriter::new(&mut output);
+    let mut deflate_reader = DeflateReader::new(BitReader::new(&mut input));
+    inflate_blocks(
+        &mut deflate_reader,
+        &mut writer,
+        &DecodeOptions::default(),
+        0,
+        0,
+    )?;
+
+    let adler32 = input.read_u32::<BigEndian>()?;
+    if adler32 != output.adler.finalize() {
+        bail!(
+            "adler32 check failed: stored={:#010x} computed={:#010x}",
+            adler32,
+            output.adler.finalize()
+        );
+    }
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adler32() {
+        assert_eq!(Adler32::new().finalize(), 1);
+
+        let mut adler = Adler32::new();
+        adler.update(b"Wiki");
+        adler.update(b"pedia");
+        assert_eq!(adler.finalize(), 0x11e60398);
+
+        // Long enough for the sums to need several reductions.
+        let mut adler = Adler32::new();
+        adler.update(&[0xff; 100_000]);
+        assert_eq!(adler.finalize(), 0x149a_302c);
+    }
+}
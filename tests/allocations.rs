@@ -0,0 +1,58 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use common::{CountingAllocator, ALLOCATIONS};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations(data: &[u8], output: &mut [u8]) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    ripgzip::decompress(data, output).unwrap();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// A gzip member with one fixed-tree block: the literals `ab`, then `count`
+/// matches of length 3 at distances 1 and 2 in turn.
+fn matches(count: usize) -> Vec<u8> {
+    // Huffman codes go most significant bit first, everything else least
+    // significant bit first.
+    let mut bits = vec![1, 1, 0];
+    let mut push_code = |code: u32, len: u32| bits.extend((0..len).rev().map(|i| (code >> i) & 1));
+    push_code(0x30 + b'a' as u32, 8);
+    push_code(0x30 + b'b' as u32, 8);
+    for i in 0..count {
+        // Length symbol 257 is length 3, distance codes 0 and 1 are 1 and 2.
+        push_code(1, 7);
+        push_code(i as u32 % 2, 5);
+    }
+    push_code(0, 7);
+
+    let mut data = b"ab".to_vec();
+    for i in 0..count {
+        for _ in 0..3 {
+            data.push(data[data.len() - 1 - i % 2]);
+        }
+    }
+    let mut member = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    member.extend(
+        bits.chunks(8)
+            .map(|byte| byte.iter().rev().fold(0, |acc, &bit| acc << 1 | bit as u8)),
+    );
+    ripgzip::make_footer(&data).write_to(&mut member).unwrap();
+    member
+}
+
+#[test]
+fn write_previous_does_not_allocate() {
+    let short = matches(1000);
+    let long = matches(2000);
+    let mut output = vec![0u8; 1 << 20];
+
+    count_allocations(&short, &mut output);
+    assert_eq!(
+        count_allocations(&short, &mut output),
+        count_allocations(&long, &mut output),
+    );
+}
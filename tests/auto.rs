@@ -0,0 +1,45 @@
+#[test]
+fn decompress_auto_gzip() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut expected = vec![];
+    ripgzip::decompress(&data[..], &mut expected).unwrap();
+
+    let mut output = vec![];
+    ripgzip::decompress_auto(&data[..], &mut output).unwrap();
+    assert_eq!(output, expected);
+
+    // The magic is recognized even if the reader hands out one byte at a time.
+    let mut output = vec![];
+    let input = std::io::BufReader::with_capacity(1, &data[..]);
+    ripgzip::decompress_auto(input, &mut output).unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn decompress_auto_raw() {
+    // The body of a gzip member, footer not included.
+    let member = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let data = &member[10..member.len() - 8];
+
+    let mut output = vec![];
+    ripgzip::decompress_auto(data, &mut output).unwrap();
+    assert_eq!(output, ripgzip::decompress_to_vec(&member[..]).unwrap());
+}
+
+#[test]
+fn inflate_raw() {
+    for member in [
+        &include_bytes!("../data/ok/00-Cargo.toml.gz")[..],
+        &include_bytes!("../data/ok/07-war-and-peace.txt.gz.gz")[..],
+    ] {
+        // Bytes after the final block are left alone.
+        let data = [&member[10..member.len() - 8], b"trailer"].concat();
+        let mut input = data.as_slice();
+        let mut output = vec![];
+        ripgzip::inflate(&mut input, &mut output).unwrap();
+        assert_eq!(output, ripgzip::decompress_to_vec(member).unwrap());
+        assert_eq!(input, b"trailer");
+    }
+
+    assert!(ripgzip::inflate(&[0xff; 4][..], std::io::sink()).is_err());
+}
@@ -0,0 +1,62 @@
+use ripgzip::CanonicalizeOptions;
+
+#[test]
+fn canonicalize_round_trip() {
+    let data = include_bytes!("../data/ok/09-concat.gz");
+    let mut expected = vec![];
+    ripgzip::decompress(&data[..], &mut expected).unwrap();
+
+    let mut canonical = vec![];
+    ripgzip::canonicalize(&data[..], &mut canonical).unwrap();
+    let mut output = vec![];
+    ripgzip::decompress(canonical.as_slice(), &mut output).unwrap();
+    assert_eq!(output, expected);
+
+    let mut again = vec![];
+    ripgzip::canonicalize(canonical.as_slice(), &mut again).unwrap();
+    assert_eq!(again, canonical);
+}
+
+#[test]
+fn canonicalize_keeps_body_verbatim() {
+    let plain = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut header = vec![0x1f, 0x8b, 8, 0b1000, 0x78, 0x56, 0x34, 0x12, 2, 3];
+    header.extend_from_slice(b"name.txt\0");
+    let named = [&header[..], &plain[10..]].concat();
+
+    let mut canonical = vec![];
+    ripgzip::canonicalize(named.as_slice(), &mut canonical).unwrap();
+    // Canonical header: FHCRC set, zero MTIME, original XFL and OS.
+    assert_eq!(&canonical[..10], [0x1f, 0x8b, 8, 0b10, 0, 0, 0, 0, 2, 3]);
+    assert_eq!(&canonical[12..], &plain[10..]);
+
+    let mut corrupt = named.clone();
+    let len = corrupt.len();
+    corrupt[len - 1] ^= 1;
+    assert!(ripgzip::canonicalize(corrupt.as_slice(), std::io::sink()).is_err());
+}
+
+#[test]
+fn canonicalize_os_and_extra_flags() {
+    let plain = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut data = plain.to_vec();
+    data[8] = 2;
+    data[9] = 3;
+    let expected = ripgzip::decompress_to_vec(data.as_slice()).unwrap();
+
+    for (normalize, xfl, os) in [(false, 2, 3), (true, 0, 255)] {
+        let options = CanonicalizeOptions {
+            normalize_os: normalize,
+            normalize_extra_flags: normalize,
+        };
+        let mut canonical = vec![];
+        ripgzip::canonicalize_with_options(data.as_slice(), &mut canonical, &options).unwrap();
+        assert_eq!(&canonical[..10], [0x1f, 0x8b, 8, 0b10, 0, 0, 0, 0, xfl, os]);
+        assert_eq!(&canonical[12..], &plain[10..]);
+        // The FHCRC covers the bytes actually written.
+        assert_eq!(
+            ripgzip::decompress_to_vec(canonical.as_slice()).unwrap(),
+            expected
+        );
+    }
+}
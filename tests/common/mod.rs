@@ -0,0 +1,254 @@
+#![allow(dead_code)]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    len: u8,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write `len` bits of `bits`, least significant bit first.
+    pub fn write_bits(&mut self, bits: u32, len: u8) {
+        for i in 0..len {
+            self.current |= (((bits >> i) & 1) as u8) << self.len;
+            self.len += 1;
+            if self.len == 8 {
+                self.bytes.push(self.current);
+                self.current = 0;
+                self.len = 0;
+            }
+        }
+    }
+
+    /// Write a Huffman code, most significant bit first.
+    pub fn write_code(&mut self, code: u32, len: u8) {
+        for i in (0..len).rev() {
+            self.write_bits((code >> i) & 1, 1);
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.current);
+        }
+        self.bytes
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug)]
+pub enum Token {
+    Literal(u8),
+    Match { len: u16, dist: u16 },
+}
+
+/// Canonical Huffman codes for `lengths`, as defined in RFC 1951 3.2.2.
+pub fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut bl_count = [0u32; 16];
+    for &len in lengths {
+        bl_count[len as usize] += 1;
+    }
+    bl_count[0] = 0;
+    let mut next_code = [0u32; 16];
+    let mut code = 0;
+    for bits in 1..16 {
+        code = (code + bl_count[bits - 1]) << 1;
+        next_code[bits] = code;
+    }
+    lengths
+        .iter()
+        .map(|&len| {
+            let code = next_code[len as usize];
+            next_code[len as usize] += 1;
+            code
+        })
+        .collect()
+}
+
+/// Order in which code length code lengths are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+#[derive(Clone, Copy, Debug)]
+pub enum CodeLength {
+    Length(u8),
+    /// Symbol 16: repeat the previous length 3-6 times.
+    CopyPrev(u8),
+    /// Symbols 17 and 18: 3-138 zero lengths.
+    Zeros(u8),
+}
+
+/// Write a dynamic block whose code lengths are sent without run-length
+/// symbols, using a complete 4-bit code length code for lengths 0-15.
+pub fn write_dynamic_block(
+    writer: &mut BitWriter,
+    litlen_lengths: &[u8],
+    dist_lengths: &[u8],
+    tokens: &[Token],
+    is_final: bool,
+) {
+    let mut cl_lengths = [4u8; 19];
+    cl_lengths[16..].fill(0);
+    let code_lengths: Vec<_> = litlen_lengths
+        .iter()
+        .chain(dist_lengths)
+        .map(|&len| CodeLength::Length(len))
+        .collect();
+    write_dynamic_block_with(
+        writer,
+        &cl_lengths,
+        &code_lengths,
+        litlen_lengths,
+        dist_lengths,
+        tokens,
+        is_final,
+    );
+}
+
+/// Write a dynamic block with an explicit code length code and code length
+/// sequence. `code_lengths` must expand to `litlen_lengths` followed by
+/// `dist_lengths`.
+pub fn write_dynamic_block_with(
+    writer: &mut BitWriter,
+    cl_lengths: &[u8; 19],
+    code_lengths: &[CodeLength],
+    litlen_lengths: &[u8],
+    dist_lengths: &[u8],
+    tokens: &[Token],
+    is_final: bool,
+) {
+    let hclen = CODE_LENGTH_ORDER
+        .iter()
+        .rposition(|&symbol| cl_lengths[symbol] != 0)
+        .map_or(4, |pos| (pos + 1).max(4));
+    writer.write_bits(is_final as u32, 1);
+    writer.write_bits(2, 2);
+    writer.write_bits(litlen_lengths.len() as u32 - 257, 5);
+    writer.write_bits(dist_lengths.len() as u32 - 1, 5);
+    writer.write_bits(hclen as u32 - 4, 4);
+    for &symbol in &CODE_LENGTH_ORDER[..hclen] {
+        writer.write_bits(cl_lengths[symbol] as u32, 3);
+    }
+
+    let cl_codes = canonical_codes(cl_lengths);
+    let write_cl = |writer: &mut BitWriter, symbol: usize| {
+        writer.write_code(cl_codes[symbol], cl_lengths[symbol])
+    };
+    for &code_length in code_lengths {
+        match code_length {
+            CodeLength::Length(len) => write_cl(writer, len as usize),
+            CodeLength::CopyPrev(count) => {
+                write_cl(writer, 16);
+                writer.write_bits(count as u32 - 3, 2);
+            }
+            CodeLength::Zeros(count) if count <= 10 => {
+                write_cl(writer, 17);
+                writer.write_bits(count as u32 - 3, 3);
+            }
+            CodeLength::Zeros(count) => {
+                write_cl(writer, 18);
+                writer.write_bits(count as u32 - 11, 7);
+            }
+        }
+    }
+
+    let litlen_codes = canonical_codes(litlen_lengths);
+    let dist_codes = canonical_codes(dist_lengths);
+    let write_litlen = |writer: &mut BitWriter, symbol: usize| {
+        writer.write_code(litlen_codes[symbol], litlen_lengths[symbol])
+    };
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => write_litlen(writer, byte as usize),
+            Token::Match { len, dist } => {
+                let idx = LENGTH_BASES.iter().rposition(|&base| base <= len).unwrap();
+                write_litlen(writer, 257 + idx);
+                writer.write_bits((len - LENGTH_BASES[idx]) as u32, LENGTH_EXTRA_BITS[idx]);
+                let idx = DIST_BASES.iter().rposition(|&base| base <= dist).unwrap();
+                writer.write_code(dist_codes[idx], dist_lengths[idx]);
+                writer.write_bits((dist - DIST_BASES[idx]) as u32, DIST_EXTRA_BITS[idx]);
+            }
+        }
+    }
+    write_litlen(writer, 256);
+}
+
+/// Expand `tokens` into the bytes they decode to.
+pub fn expand(tokens: &[Token]) -> Vec<u8> {
+    let mut data = vec![];
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => data.push(byte),
+            Token::Match { len, dist } => {
+                for _ in 0..len {
+                    data.push(data[data.len() - dist as usize]);
+                }
+            }
+        }
+    }
+    data
+}
+
+/// Wrap a DEFLATE body into a gzip member with a minimal header.
+pub fn gzip_member(body: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut member = [&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255][..], body].concat();
+    ripgzip::make_footer(data).write_to(&mut member).unwrap();
+    member
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, ripgzip::DecodeError> {
+    let mut output = vec![];
+    ripgzip::decompress(data, &mut output)?;
+    Ok(output)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts every allocation in `ALLOCATIONS`. Install it with
+/// `#[global_allocator]` in the test or bench that needs it.
+pub struct CountingAllocator;
+
+pub static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
@@ -0,0 +1,283 @@
+use ripgzip::{DecodeOptions, GzDecoder};
+
+fn decode_in_chunks(data: &[u8], chunk_size: usize) -> Vec<u8> {
+    let mut decoder = GzDecoder::new(data);
+    let mut output = vec![];
+    let mut buf = vec![0u8; chunk_size];
+    loop {
+        let size = decoder.decode_into_buffer(&mut buf).unwrap();
+        if size == 0 {
+            break;
+        }
+        assert!(size <= chunk_size);
+        output.extend_from_slice(&buf[..size]);
+    }
+    output
+}
+
+#[test]
+fn decode_into_buffer() {
+    for data in [
+        &include_bytes!("../data/ok/00-Cargo.toml.gz")[..],
+        &include_bytes!("../data/ok/01-page.gz")[..],
+        &include_bytes!("../data/ok/09-concat.gz")[..],
+    ] {
+        let mut expected = vec![];
+        ripgzip::decompress(data, &mut expected).unwrap();
+        assert_eq!(decode_in_chunks(data, 16), expected);
+        assert_eq!(decode_in_chunks(data, 1), expected);
+    }
+}
+
+#[test]
+fn decode_into_buffer_reports_corruption() {
+    let data = include_bytes!("../data/corrupted/01-bad-crc32.gz");
+    let mut decoder = GzDecoder::new(&data[..]);
+    let mut buf = [0u8; 16];
+    let err = loop {
+        match decoder.decode_into_buffer(&mut buf) {
+            Ok(0) => panic!("expected Err, got end of stream"),
+            Ok(_) => continue,
+            Err(err) => break err,
+        }
+    };
+    assert!(err.to_string().contains("crc32 check failed"));
+}
+
+#[test]
+fn decompress_iter() {
+    let data = include_bytes!("../data/ok/09-concat.gz");
+    let mut expected = vec![];
+    ripgzip::decompress(&data[..], &mut expected).unwrap();
+
+    let chunks = ripgzip::decompress_iter(&data[..])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(!chunks.is_empty());
+    assert_eq!(chunks.concat(), expected);
+
+    let data = include_bytes!("../data/corrupted/01-bad-crc32.gz");
+    let last = ripgzip::decompress_iter(&data[..]).last().unwrap();
+    assert!(last.is_err());
+}
+
+#[test]
+fn last_stats() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let options = DecodeOptions {
+        measure_time: true,
+        ..DecodeOptions::default()
+    };
+    let mut decoder = GzDecoder::with_options(&data[..], options);
+    let mut buf = [0u8; 64];
+    while decoder.decode_into_buffer(&mut buf).unwrap() > 0 {}
+
+    let stats = decoder.last_stats();
+    assert_eq!(stats.blocks, 1);
+    // Literals and lengths, the end of block included.
+    assert_eq!(stats.symbols, 233);
+    // The whole body, padded to a byte boundary before the footer.
+    assert_eq!(stats.bits_read, (data.len() - 18) as u64 * 8);
+    assert!(stats.elapsed.is_some());
+
+    let mut decoder = GzDecoder::new(&data[..]);
+    while decoder.decode_into_buffer(&mut buf).unwrap() > 0 {}
+    assert_eq!(decoder.last_stats().elapsed, None);
+}
+
+#[test]
+fn read_lazily() {
+    use std::io::Read;
+
+    let data = include_bytes!("../data/ok/06-war-and-peace.txt.gz");
+    let expected = ripgzip::decompress_to_vec(&data[..]).unwrap();
+
+    let mut input = &data[..];
+    let mut decoder = GzDecoder::new(&mut input);
+    let mut block = [0u8; 512];
+    decoder.read_exact(&mut block).unwrap();
+    assert_eq!(block, expected[..512]);
+    drop(decoder);
+    assert!(input.len() > data.len() * 9 / 10, "{} left", input.len());
+
+    let mut decoder = GzDecoder::new(&data[..]);
+    decoder.read_exact(&mut block).unwrap();
+    let rest = std::io::copy(&mut decoder, &mut std::io::sink()).unwrap();
+    assert_eq!(rest as usize, expected.len() - 512);
+}
+
+#[test]
+fn read_one_byte_at_a_time() {
+    use std::io::Read;
+
+    let data = include_bytes!("../data/ok/01-page.gz");
+    let mut decoder = GzDecoder::new(&data[..]);
+    let mut output = vec![];
+    let mut byte = [0u8];
+    while decoder.read(&mut byte).unwrap() > 0 {
+        output.push(byte[0]);
+    }
+    assert_eq!(output, ripgzip::decompress_to_vec(&data[..]).unwrap());
+}
+
+/// Everything the decoder hands out, and how it stopped.
+fn drain<R: std::io::BufRead>(
+    decoder: &mut GzDecoder<R>,
+) -> (Vec<u8>, Result<(), ripgzip::DecodeError>) {
+    let mut output = vec![];
+    let mut buf = [0u8; 64];
+    loop {
+        match decoder.decode_into_buffer(&mut buf) {
+            Ok(0) => return (output, Ok(())),
+            Ok(size) => output.extend_from_slice(&buf[..size]),
+            Err(err) => return (output, Err(err)),
+        }
+    }
+}
+
+#[test]
+fn corrupt_magic_in_later_member() {
+    let member = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut data = member.repeat(2);
+    data[member.len() + 1] = 0x8c;
+
+    let (output, result) = drain(&mut GzDecoder::new(data.as_slice()));
+    let expected = ripgzip::decompress_to_vec(&member[..]).unwrap();
+    assert!(expected.starts_with(&output));
+    assert!(matches!(
+        result.unwrap_err(),
+        ripgzip::DecodeError::CorruptMemberMagic
+    ));
+}
+
+#[test]
+fn stop_after_last_member() {
+    let member = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let data = [&member[..], &member[..], b"trailing metadata"].concat();
+    assert!(drain(&mut GzDecoder::new(data.as_slice())).1.is_err());
+
+    let options = DecodeOptions {
+        stop_after_last_member: true,
+        ..DecodeOptions::default()
+    };
+    let mut input = data.as_slice();
+    let (output, result) = drain(&mut GzDecoder::with_options(&mut input, options));
+    result.unwrap();
+    assert_eq!(
+        output,
+        ripgzip::decompress_to_vec(&member.repeat(2)[..]).unwrap()
+    );
+    assert_eq!(input, b"trailing metadata");
+}
+
+#[test]
+fn checksum_error_policy() {
+    use ripgzip::{ChecksumErrorPolicy, Warning};
+
+    let good = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let bad = include_bytes!("../data/corrupted/01-bad-crc32.gz");
+    let expected = ripgzip::decompress_to_vec(&good[..]).unwrap();
+    let data = [&bad[..], &good[..]].concat();
+
+    let options = DecodeOptions {
+        on_checksum_error: ChecksumErrorPolicy::KeepAndWarn,
+        ..DecodeOptions::default()
+    };
+    let mut decoder = GzDecoder::with_options(data.as_slice(), options);
+    let (output, result) = drain(&mut decoder);
+    result.unwrap();
+    assert_eq!(output.len(), 2 * expected.len());
+    assert_eq!(decoder.warnings(), [Warning::ChecksumMismatch(0)]);
+
+    let options = DecodeOptions {
+        on_checksum_error: ChecksumErrorPolicy::Skip,
+        buffer_until_verified: Some(1 << 10),
+        ..DecodeOptions::default()
+    };
+    let (output, result) = drain(&mut GzDecoder::with_options(data.as_slice(), options));
+    result.unwrap();
+    assert_eq!(output, expected);
+
+    let options = DecodeOptions {
+        on_checksum_error: ChecksumErrorPolicy::Skip,
+        ..DecodeOptions::default()
+    };
+    assert!(
+        drain(&mut GzDecoder::with_options(data.as_slice(), options))
+            .1
+            .is_err()
+    );
+
+    // Nothing of a member is handed out before its footer is checked.
+    let options = DecodeOptions {
+        buffer_until_verified: Some(1 << 10),
+        ..DecodeOptions::default()
+    };
+    let (output, result) = drain(&mut GzDecoder::with_options(&bad[..], options));
+    assert!(output.is_empty());
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("crc32 check failed"));
+
+    let options = DecodeOptions {
+        buffer_until_verified: Some(expected.len() - 1),
+        ..DecodeOptions::default()
+    };
+    let (output, result) = drain(&mut GzDecoder::with_options(&good[..], options));
+    assert!(output.is_empty());
+    assert!(result.unwrap_err().to_string().contains("limit exceeded"));
+}
+
+#[test]
+fn decode_into_empty_buffer() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut decoder = GzDecoder::new(&data[..]);
+    assert_eq!(decoder.decode_into_buffer(&mut []).unwrap(), 0);
+    assert_eq!(decoder.last_stats().blocks, 0);
+    let (output, result) = drain(&mut decoder);
+    result.unwrap();
+    assert_eq!(output, ripgzip::decompress_to_vec(&data[..]).unwrap());
+}
+
+#[test]
+fn events_for_dynamic_and_stored_blocks() {
+    use ripgzip::{CompressionType, DecodeEvent};
+
+    let data = include_bytes!("../data/ok/03-photo.jpg.gz");
+    let events = GzDecoder::new(&data[..])
+        .events()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let mut output = vec![];
+    let mut kinds = vec![];
+    let mut in_block = false;
+    for event in events {
+        match event {
+            DecodeEvent::BlockStart(kind) => {
+                assert!(!in_block);
+                in_block = true;
+                kinds.push(kind);
+            }
+            DecodeEvent::Chunk(chunk) => {
+                assert!(in_block);
+                output.extend(chunk);
+            }
+            DecodeEvent::BlockEnd => {
+                assert!(in_block);
+                in_block = false;
+            }
+        }
+    }
+    assert!(!in_block);
+    assert!(kinds.contains(&CompressionType::DynamicTree));
+    assert!(kinds.contains(&CompressionType::Uncompressed));
+    assert_eq!(output, ripgzip::decompress_to_vec(&data[..]).unwrap());
+
+    let mut corrupt = data.to_vec();
+    let len = corrupt.len();
+    corrupt[len - 8] ^= 1;
+    let last = GzDecoder::new(corrupt.as_slice()).events().last().unwrap();
+    assert!(last.unwrap_err().to_string().contains("crc32 check failed"));
+}
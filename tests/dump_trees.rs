@@ -0,0 +1,41 @@
+#![cfg(feature = "dump-trees")]
+
+use ripgzip::BitSequence;
+
+/// Check that `codes` are the canonical Huffman codes for `lengths`, as
+/// defined in RFC 1951 3.2.2: ordered by length, then by symbol, each code
+/// is one more than the previous one, shifted to its length.
+fn check_canonical(lengths: &[u8], codes: &[(u16, BitSequence)]) {
+    let used: Vec<_> = (0..lengths.len() as u16)
+        .filter(|&symbol| lengths[symbol as usize] != 0)
+        .collect();
+    let symbols: Vec<_> = codes.iter().map(|&(symbol, _code)| symbol).collect();
+    assert_eq!(symbols, used);
+
+    let mut sorted: Vec<_> = codes
+        .iter()
+        .map(|&(symbol, code)| {
+            assert_eq!(code.len(), lengths[symbol as usize]);
+            (code.len(), symbol, code.bits() as u32)
+        })
+        .collect();
+    sorted.sort();
+    for pair in sorted.windows(2) {
+        let ((prev_len, _, prev_code), (len, _, code)) = (pair[0], pair[1]);
+        assert_eq!(code, (prev_code + 1) << (len - prev_len));
+    }
+}
+
+#[test]
+fn dump_dynamic_trees() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let dumps = ripgzip::dump_trees(&data[..]).unwrap();
+    assert_eq!(dumps.len(), 1);
+    assert_ne!(dumps[0].litlen_lengths[256], 0);
+    check_canonical(&dumps[0].litlen_lengths, &dumps[0].litlen_codes);
+    check_canonical(&dumps[0].dist_lengths, &dumps[0].dist_codes);
+
+    // One dump per dynamic block, none for the stored ones.
+    let data = include_bytes!("../data/ok/03-photo.jpg.gz");
+    assert_eq!(ripgzip::dump_trees(&data[..]).unwrap().len(), 11);
+}
@@ -0,0 +1,37 @@
+use std::io::{BufRead, Cursor, Write};
+
+#[test]
+fn decompress_dyn() {
+    let data = include_bytes!("../data/ok/09-concat.gz");
+    let mut expected = vec![];
+    ripgzip::decompress(&data[..], &mut expected).unwrap();
+
+    let mut output = vec![];
+    {
+        let mut input: Box<dyn BufRead> = Box::new(Cursor::new(&data[..]));
+        let mut sink: Box<dyn Write + '_> = Box::new(&mut output);
+        ripgzip::decompress_dyn(&mut input, &mut sink).unwrap();
+    }
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn boxed_dyn_reader() {
+    let data = include_bytes!("../data/ok/09-concat.gz");
+    let expected = ripgzip::decompress_to_vec(&data[..]).unwrap();
+
+    let input = Box::new(Cursor::new(data.to_vec())) as Box<dyn BufRead>;
+    assert_eq!(ripgzip::decompress_to_vec(input).unwrap(), expected);
+
+    let input = Box::new(Cursor::new(data.to_vec())) as Box<dyn BufRead>;
+    let mut decoder = ripgzip::GzDecoder::new(input);
+    let mut output = vec![0; expected.len() + 1];
+    let mut size = 0;
+    loop {
+        match decoder.decode_into_buffer(&mut output[size..]).unwrap() {
+            0 => break,
+            read => size += read,
+        }
+    }
+    assert_eq!(&output[..size], expected);
+}
@@ -0,0 +1,247 @@
+mod common;
+
+use common::{
+    decompress, expand, gzip_member, write_dynamic_block, write_dynamic_block_with, BitWriter,
+    CodeLength, Token,
+};
+
+#[test]
+fn literal_only_block_with_empty_distance_tree() {
+    let mut litlen_lengths = vec![0u8; 257];
+    for symbol in [b'a' as usize, b'b' as usize, b'c' as usize, 256] {
+        litlen_lengths[symbol] = 2;
+    }
+    let tokens: Vec<_> = b"abcabccba".iter().map(|&b| Token::Literal(b)).collect();
+
+    let mut writer = BitWriter::new();
+    write_dynamic_block(&mut writer, &litlen_lengths, &[0], &tokens, true);
+    let data = gzip_member(&writer.finish(), b"abcabccba");
+
+    assert_eq!(decompress(&data).unwrap(), b"abcabccba");
+}
+
+#[test]
+fn match_with_empty_distance_tree_fails() {
+    let mut litlen_lengths = vec![0u8; 258];
+    for symbol in [b'a' as usize, b'b' as usize, 256, 257] {
+        litlen_lengths[symbol] = 2;
+    }
+    let tokens = [
+        Token::Literal(b'a'),
+        Token::Literal(b'b'),
+        Token::Match { len: 3, dist: 1 },
+    ];
+
+    let mut writer = BitWriter::new();
+    write_dynamic_block(&mut writer, &litlen_lengths, &[0], &tokens, true);
+    let data = gzip_member(&writer.finish(), b"abbbb");
+
+    assert!(decompress(&data).is_err());
+}
+
+#[test]
+fn distance_codes_30_and_31_are_rejected() {
+    let mut litlen_lengths = vec![0u8; 257];
+    for symbol in [b'a' as usize, 256] {
+        litlen_lengths[symbol] = 1;
+    }
+    let tokens = [Token::Literal(b'a')];
+
+    for symbol in [30, 31] {
+        let mut dist_lengths = vec![0u8; 32];
+        dist_lengths[0] = 1;
+        dist_lengths[symbol] = 1;
+
+        let mut writer = BitWriter::new();
+        write_dynamic_block(&mut writer, &litlen_lengths, &dist_lengths, &tokens, true);
+        let data = gzip_member(&writer.finish(), b"a");
+
+        let err = decompress(&data).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("invalid distance code {}", symbol)),
+            "{}",
+            err
+        );
+    }
+}
+
+/// Run-length encode `lengths` with `CopyPrev` and `Zeros` where possible.
+fn run_length_encode(lengths: &[u8]) -> Vec<CodeLength> {
+    let mut code_lengths = vec![];
+    let mut i = 0;
+    while i < lengths.len() {
+        let len = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == len).count();
+        i += run;
+        let mut left = run;
+        if len == 0 && left >= 3 {
+            code_lengths.push(CodeLength::Zeros(left as u8));
+            continue;
+        }
+        code_lengths.push(CodeLength::Length(len));
+        left -= 1;
+        while left >= 3 {
+            let mut count = left.min(6);
+            if (1..3).contains(&(left - count)) {
+                count = left - 3;
+            }
+            code_lengths.push(CodeLength::CopyPrev(count as u8));
+            left -= count;
+        }
+        code_lengths.extend((0..left).map(|_| CodeLength::Length(len)));
+    }
+    code_lengths
+}
+
+#[test]
+fn maximal_dynamic_header() {
+    // HCLEN = 19: every code length code gets a length, 13 of 4 bits and 6
+    // of 5 bits make a complete code.
+    let mut cl_lengths = [4u8; 19];
+    for symbol in [0, 1, 2, 3, 5, 6] {
+        cl_lengths[symbol] = 5;
+    }
+
+    // HLIT = 286: 231 codes of 8 bits and 50 of 9 bits, with a gap of five
+    // unused literals, sent via symbol 17.
+    let mut litlen_lengths = vec![8u8; 231];
+    litlen_lengths.extend([9; 19]);
+    litlen_lengths.extend([0; 5]);
+    litlen_lengths.extend([9; 31]);
+    assert_eq!(litlen_lengths.len(), 286);
+
+    // HDIST = 30: codes 0-15 of 4 bits, the rest unused via symbol 18.
+    let mut dist_lengths = vec![4u8; 16];
+    dist_lengths.extend([0; 14]);
+
+    let lengths = [&litlen_lengths[..], &dist_lengths[..]].concat();
+    let code_lengths = run_length_encode(&lengths);
+    for symbol in [16, 17, 18] {
+        assert!(code_lengths.iter().any(|&code_length| match code_length {
+            CodeLength::CopyPrev(_) => symbol == 16,
+            CodeLength::Zeros(count) => symbol == 17 + (count > 10) as usize,
+            CodeLength::Length(_) => false,
+        }));
+    }
+
+    let mut tokens: Vec<_> = (0..=255u8)
+        .filter(|&byte| litlen_lengths[byte as usize] != 0)
+        .map(Token::Literal)
+        .collect();
+    tokens.extend([
+        Token::Match { len: 258, dist: 1 },
+        Token::Match { len: 3, dist: 256 },
+        Token::Match {
+            len: 100,
+            dist: 200,
+        },
+        Token::Match {
+            len: 227,
+            dist: 193,
+        },
+    ]);
+    let expected = expand(&tokens);
+
+    let mut writer = BitWriter::new();
+    write_dynamic_block_with(
+        &mut writer,
+        &cl_lengths,
+        &code_lengths,
+        &litlen_lengths,
+        &dist_lengths,
+        &tokens,
+        true,
+    );
+    let data = gzip_member(&writer.finish(), &expected);
+
+    assert_eq!(decompress(&data).unwrap(), expected);
+}
+
+/// Code length code for lengths 0-5 and symbols 16 and 17, 3 bits each.
+fn small_cl_lengths() -> [u8; 19] {
+    let mut cl_lengths = [0u8; 19];
+    for symbol in [0, 1, 2, 3, 4, 5, 16, 17] {
+        cl_lengths[symbol] = 3;
+    }
+    cl_lengths
+}
+
+#[test]
+fn leading_repeat_zero() {
+    let mut cl_lengths = small_cl_lengths();
+    cl_lengths[17] = 0;
+    cl_lengths[18] = 3;
+    let mut litlen_lengths = vec![0u8; 257];
+    litlen_lengths[b'a' as usize] = 1;
+    litlen_lengths[256] = 1;
+    let code_lengths = [
+        CodeLength::Zeros(97),
+        CodeLength::Length(1),
+        CodeLength::Zeros(138),
+        CodeLength::Zeros(20),
+        CodeLength::Length(1),
+        CodeLength::Length(1),
+    ];
+
+    let mut writer = BitWriter::new();
+    write_dynamic_block_with(
+        &mut writer,
+        &cl_lengths,
+        &code_lengths,
+        &litlen_lengths,
+        &[1],
+        &[Token::Literal(b'a')],
+        true,
+    );
+    let data = gzip_member(&writer.finish(), b"a");
+
+    assert_eq!(decompress(&data).unwrap(), b"a");
+}
+
+#[test]
+fn leading_copy_prev_fails() {
+    let mut litlen_lengths = vec![1u8; 3];
+    litlen_lengths.resize(257, 0);
+    let code_lengths = [
+        CodeLength::CopyPrev(3),
+        CodeLength::Zeros(10),
+        CodeLength::Zeros(10),
+    ];
+
+    let mut writer = BitWriter::new();
+    write_dynamic_block_with(
+        &mut writer,
+        &small_cl_lengths(),
+        &code_lengths,
+        &litlen_lengths,
+        &[0],
+        &[],
+        true,
+    );
+    let data = gzip_member(&writer.finish(), b"");
+
+    let err = decompress(&data).unwrap_err();
+    assert!(err.to_string().contains("invalid tree"), "{}", err);
+}
+
+#[test]
+fn single_one_bit_distance_code() {
+    let mut litlen_lengths = vec![0u8; 258];
+    for symbol in [b'a' as usize, b'b' as usize, 256, 257] {
+        litlen_lengths[symbol] = 2;
+    }
+    let tokens = [
+        Token::Literal(b'a'),
+        Token::Literal(b'b'),
+        Token::Match { len: 3, dist: 2 },
+    ];
+    let mut dist_lengths = vec![0u8; 2];
+    dist_lengths[1] = 1;
+
+    let mut writer = BitWriter::new();
+    write_dynamic_block(&mut writer, &litlen_lengths, &dist_lengths, &tokens, true);
+    let data = gzip_member(&writer.finish(), b"ababa");
+
+    assert_eq!(decompress(&data).unwrap(), b"ababa");
+}
@@ -1,13 +1,25 @@
+mod common;
+
+use common::{gzip_member, BitWriter};
+
+const CARGO: &[u8] = include_bytes!("../data/ok/00-Cargo.toml.gz");
+
+/// The length of `00-Cargo.toml` itself.
+const CARGO_LEN: usize = 295;
+
 fn check_decompression_error(mut data: &[u8], msg: &'static str) {
     let res = ripgzip::decompress(&mut data, &mut std::io::sink());
     if res.is_ok() {
         panic!("expected Err, got Ok");
     }
-    for inner in res.unwrap_err().chain() {
-        println!("Error message: {}", inner.to_string());
-        if inner.to_string().contains(msg) {
+    let err = res.unwrap_err();
+    let mut inner: Option<&dyn std::error::Error> = Some(&err);
+    while let Some(cause) = inner {
+        println!("Error message: {}", cause);
+        if cause.to_string().contains(msg) {
             return;
         }
+        inner = cause.source();
     }
     panic!("error does not contain message: {}", msg);
 }
@@ -45,3 +57,308 @@ fn errors() {
         "nlen check failed",
     );
 }
+
+#[test]
+fn empty_isize_with_nonempty_data() {
+    let mut data = CARGO.to_vec();
+    let len = data.len();
+    data[len - 4..].copy_from_slice(&0u32.to_le_bytes());
+
+    let err = ripgzip::decompress(data.as_slice(), std::io::sink()).unwrap_err();
+    let msg = err.to_string();
+    assert!(msg.contains("expected=0 actual=295"), "{}", msg);
+    assert!(msg.contains("empty member"), "{}", msg);
+}
+
+#[test]
+fn zlib_body_inside_gzip_member() {
+    let data = [&CARGO[..10], &[0x78, 0x9c], &CARGO[10..]].concat();
+
+    let err = ripgzip::decompress(data.as_slice(), std::io::sink()).unwrap_err();
+    assert!(err.to_string().contains("may be zlib"), "{:#}", err);
+
+    let mut decoder = ripgzip::GzDecoder::new(data.as_slice());
+    let err = decoder.decode_into_buffer(&mut [0u8; 64]).unwrap_err();
+    assert!(err.to_string().contains("may be zlib"), "{:#}", err);
+}
+
+#[test]
+fn not_gzip_at_stream_start() {
+    let err = ripgzip::decompress(&b"PK\x03\x04 not gzip"[..], std::io::sink()).unwrap_err();
+    assert!(matches!(err, ripgzip::DecodeError::BadMagic), "{}", err);
+}
+
+#[test]
+fn corrupt_magic_in_later_member() {
+    let mut data = CARGO.repeat(2);
+    data[CARGO.len() + 1] = 0x8c;
+
+    let err = ripgzip::decompress(data.as_slice(), std::io::sink()).unwrap_err();
+    assert!(
+        matches!(err, ripgzip::DecodeError::CorruptMemberMagic),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn header_without_body() {
+    check_decompression_error(&CARGO[..10], "gzip header present but no DEFLATE data");
+
+    let err = ripgzip::decompress(&CARGO[..11], std::io::sink()).unwrap_err();
+    assert!(!err.to_string().contains("no DEFLATE data"), "{:#}", err);
+
+    let err = ripgzip::decompress_iter(&CARGO[..10])
+        .find_map(Result::err)
+        .unwrap();
+    assert!(err.to_string().contains("no DEFLATE data"), "{:#}", err);
+}
+
+#[test]
+fn unsupported_method_in_later_member() {
+    let first = ripgzip::decompress_to_vec(CARGO).unwrap();
+    let mut data = CARGO.repeat(2);
+    data[CARGO.len() + 2] = 9;
+
+    let (output, err) = ripgzip::decompress_salvage(data.as_slice());
+    assert_eq!(output, first);
+    assert!(matches!(
+        err,
+        Some(ripgzip::DecodeError::UnsupportedMethod(9))
+    ));
+
+    let mut output = vec![];
+    assert!(ripgzip::decompress(data.as_slice(), &mut output).is_err());
+    assert_eq!(output, first);
+
+    let mut decoder = ripgzip::GzDecoder::new(data.as_slice());
+    let mut buf = vec![0u8; CARGO_LEN];
+    assert_eq!(decoder.decode_into_buffer(&mut buf).unwrap(), CARGO_LEN);
+    assert_eq!(buf, first);
+    let err = decoder.decode_into_buffer(&mut buf).unwrap_err();
+    assert!(matches!(err, ripgzip::DecodeError::UnsupportedMethod(9)));
+}
+
+#[test]
+fn salvage_output_before_corruption() {
+    let good = ripgzip::decompress_to_vec(CARGO).unwrap();
+    // A fixed block starting with a match at distance 1, with nothing before
+    // it in its member.
+    let data = [CARGO, &CARGO[..10], &[0x03, 0x02, 0x00], &[0; 8]].concat();
+
+    let (output, err) = ripgzip::decompress_salvage(data.as_slice());
+    assert_eq!(output, good);
+    assert!(err.unwrap().to_string().contains("bad dist"));
+
+    let data = include_bytes!("../data/corrupted/02-unexpected-eof.gz");
+    let (output, err) = ripgzip::decompress_salvage(&data[..]);
+    assert!(!output.is_empty());
+    assert!(matches!(err, Some(ripgzip::DecodeError::Truncated)));
+
+    let (output, err) = ripgzip::decompress_salvage(CARGO);
+    assert_eq!(output, good);
+    assert!(err.is_none());
+
+    // A single member whose fixed block holds the first 500 bytes as
+    // literals, then a match reaching back past the start of the output.
+    let original: Vec<u8> = (0..1000).map(|i| b'a' + (i % 26) as u8).collect();
+    let mut writer = BitWriter::new();
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+    for &byte in &original[..500] {
+        writer.write_code(0x30 + byte as u32, 8);
+    }
+    // Length symbol 257, then distance code 29 for a distance of 24577.
+    writer.write_code(1, 7);
+    writer.write_code(29, 5);
+    writer.write_bits(0, 13);
+    writer.write_code(0, 7);
+    let data = gzip_member(&writer.finish(), &original);
+
+    let (output, err) = ripgzip::decompress_salvage(data.as_slice());
+    assert_eq!(output, original[..500]);
+    assert!(err.is_some());
+}
+
+#[test]
+fn raw_header_on_crc_mismatch() {
+    let good = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let bad = include_bytes!("../data/corrupted/05-bad-header-crc16.gz");
+
+    let err = ripgzip::decompress(&bad[..], std::io::sink()).unwrap_err();
+    let ripgzip::DecodeError::HeaderCrcMismatch(mismatch) = err else {
+        panic!("expected HeaderCrcMismatch, got {}", err);
+    };
+    assert_eq!(mismatch.raw_header, None);
+    let header_len = mismatch.header_len;
+
+    let data = [&b"junk!"[..], good, bad].concat();
+    let mut input = std::io::Cursor::new(data);
+    input.set_position(5);
+    let err = ripgzip::decompress_seekable(input, std::io::sink()).unwrap_err();
+    assert!(err.to_string().contains("header bytes: [1f, 8b"), "{}", err);
+    let ripgzip::DecodeError::HeaderCrcMismatch(mismatch) = err else {
+        panic!("expected HeaderCrcMismatch, got {}", err);
+    };
+    assert_eq!(mismatch.raw_header.as_deref(), Some(&bad[..header_len]));
+
+    let mut output = vec![];
+    ripgzip::decompress_seekable(std::io::Cursor::new(good), &mut output).unwrap();
+    assert_eq!(output, ripgzip::decompress_to_vec(&good[..]).unwrap());
+}
+
+#[test]
+fn io_error_kinds() {
+    use std::io::ErrorKind;
+
+    let decompress_io = |data: &[u8]| ripgzip::decompress_io(data, std::io::sink()).unwrap_err();
+
+    let err = decompress_io(include_bytes!("../data/corrupted/01-bad-crc32.gz"));
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().contains("crc32 check failed"), "{}", err);
+
+    let err = decompress_io(&CARGO[..CARGO.len() - 50]);
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    let err = decompress_io(&CARGO[..CARGO.len() - 4]);
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+
+    let mut output = vec![];
+    ripgzip::decompress_io(CARGO, &mut output).unwrap();
+    assert_eq!(output, ripgzip::decompress_to_vec(CARGO).unwrap());
+
+    let err = ripgzip::decompress_io(CARGO, &mut [0u8; 10][..]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WriteZero);
+}
+
+#[test]
+fn output_limit() {
+    let bomb = include_bytes!("../data/ok/06-war-and-peace.txt.gz");
+    let size = ripgzip::decompress_to_vec(&bomb[..]).unwrap().len() as u64;
+
+    let mut output = vec![];
+    let err = ripgzip::decompress_limited(&bomb[..], &mut output, 1000).unwrap_err();
+    let ripgzip::DecodeError::OutputLimitExceeded(exceeded) = err else {
+        panic!("expected OutputLimitExceeded, got {}", err);
+    };
+    assert_eq!(exceeded.limit, 1000);
+    assert_eq!(exceeded.produced, output.len() as u64);
+    assert!(output.len() <= 1000);
+    ripgzip::decompress_limited(&bomb[..], std::io::sink(), size).unwrap();
+
+    // The limit covers all members together, stored blocks included.
+    let data = CARGO.repeat(2);
+    let limit = CARGO_LEN as u64 + 10;
+    let mut output = vec![];
+    let err = ripgzip::decompress_limited(data.as_slice(), &mut output, limit).unwrap_err();
+    let ripgzip::DecodeError::OutputLimitExceeded(exceeded) = err else {
+        panic!("expected OutputLimitExceeded, got {}", err);
+    };
+    assert_eq!(exceeded.limit, limit);
+    assert_eq!(exceeded.produced, output.len() as u64);
+    assert!(output.starts_with(&ripgzip::decompress_to_vec(CARGO).unwrap()));
+
+    let data = include_bytes!("../data/ok/03-photo.jpg.gz");
+    let size = ripgzip::decompress_to_vec(&data[..]).unwrap().len() as u64;
+    let err = ripgzip::decompress_limited(&data[..], std::io::sink(), size - 1).unwrap_err();
+    assert!(matches!(err, ripgzip::DecodeError::OutputLimitExceeded(_)));
+
+    let options = ripgzip::DecodeOptions {
+        max_output_bytes: Some(1000),
+        ..ripgzip::DecodeOptions::default()
+    };
+    let mut decoder = ripgzip::GzDecoder::with_options(&bomb[..], options);
+    let mut buf = vec![0u8; 1 << 16];
+    let err = decoder.decode_into_buffer(&mut buf).unwrap_err();
+    assert!(matches!(err, ripgzip::DecodeError::OutputLimitExceeded(_)));
+}
+
+#[test]
+fn truncated_header() {
+    // FTEXT, FHCRC, FEXTRA, FNAME and FCOMMENT all set.
+    let mut header = vec![0x1f, 0x8b, 8, 0b11111, 1, 2, 3, 4, 0, 3, 4, 0];
+    header.extend_from_slice(b"AB\x02\x00");
+    header.extend_from_slice(b"name\0comment\0");
+    let crc16 = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(&header) as u16;
+    header.extend_from_slice(&crc16.to_le_bytes());
+    let data = [&header[..], &CARGO[10..]].concat();
+    assert_eq!(
+        ripgzip::decompress_to_vec(data.as_slice()).unwrap(),
+        ripgzip::decompress_to_vec(CARGO).unwrap()
+    );
+
+    for len in 1..header.len() {
+        let truncated = &data[..len];
+        assert!(ripgzip::decompress(truncated, std::io::sink()).is_err());
+        let mut decoder = ripgzip::GzDecoder::new(truncated);
+        assert!(decoder.decode_into_buffer(&mut [0u8; 16]).is_err());
+        let mut reader = ripgzip::GzipReader::new(truncated);
+        assert!(reader.read_header().unwrap().is_err(), "{} bytes", len);
+    }
+}
+
+#[test]
+fn typed_errors() {
+    use ripgzip::DecodeError;
+
+    let kind = |data: &[u8]| ripgzip::decompress(data, std::io::sink()).unwrap_err();
+
+    assert!(matches!(
+        kind(include_bytes!("../data/corrupted/00-bad-length.gz")),
+        DecodeError::LengthMismatch { .. }
+    ));
+    assert!(matches!(
+        kind(include_bytes!("../data/corrupted/01-bad-crc32.gz")),
+        DecodeError::Crc32Mismatch { .. }
+    ));
+    assert!(matches!(
+        kind(include_bytes!("../data/corrupted/02-unexpected-eof.gz")),
+        DecodeError::Truncated
+    ));
+
+    let mut data = CARGO.to_vec();
+    let len = data.len();
+    data[len - 4..].copy_from_slice(&3u32.to_le_bytes());
+    assert!(matches!(
+        kind(&data),
+        DecodeError::LengthMismatch {
+            expected: 3,
+            found: 295,
+        }
+    ));
+
+    // A fixed block: the literal 'a', then a match at distance 2.
+    let data = [&CARGO[..10], &[0x4b, 0x04, 0x42, 0x00], &[0; 8]].concat();
+    assert!(matches!(
+        kind(&data),
+        DecodeError::BadDistance {
+            distance: 2,
+            available: 1,
+        }
+    ));
+
+    // Truncation anywhere, header string fields included.
+    let data = [
+        &CARGO[..3],
+        &[0b1000],
+        &CARGO[4..10],
+        b"name\0",
+        &CARGO[10..],
+    ]
+    .concat();
+    for len in 1..data.len() {
+        let truncated = &data[..len];
+        let err = ripgzip::decompress(truncated, std::io::sink()).unwrap_err();
+        if err.to_string().contains("no DEFLATE data") {
+            continue;
+        }
+        assert!(
+            matches!(err, DecodeError::Truncated),
+            "{} bytes: {}",
+            len,
+            err
+        );
+    }
+    let mut decoder = ripgzip::GzDecoder::new(&data[..data.len() - 2]);
+    let err = std::io::copy(&mut decoder, &mut std::io::sink()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
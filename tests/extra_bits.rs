@@ -0,0 +1,112 @@
+mod common;
+
+use common::{gzip_member, BitWriter};
+
+const PREFIX: &[u8] = b"abcdefgh";
+
+/// Fixed-tree block: the `PREFIX` literals, then one match written out bit
+/// by bit.
+fn match_block(
+    len_symbol: u32,
+    len_extra: (u32, u8),
+    dist_symbol: u32,
+    dist_extra: (u32, u8),
+) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+    for &byte in PREFIX {
+        writer.write_code(0x30 + byte as u32, 8);
+    }
+    writer.write_code(len_symbol - 256, 7);
+    writer.write_bits(len_extra.0, len_extra.1);
+    writer.write_code(dist_symbol, 5);
+    writer.write_bits(dist_extra.0, dist_extra.1);
+    writer.write_code(0, 7);
+    writer.finish()
+}
+
+fn decode(body: &[u8], len: u16, dist: u16) -> (Vec<u8>, Vec<u8>) {
+    let mut expected = PREFIX.to_vec();
+    for _ in 0..len {
+        expected.push(expected[expected.len() - dist as usize]);
+    }
+    let data = gzip_member(body, &expected);
+    (
+        ripgzip::decompress_to_vec(data.as_slice()).unwrap(),
+        expected,
+    )
+}
+
+#[test]
+fn length_extra_bits() {
+    // Symbol 269: base 19, 2 extra bits.
+    for extra in 0..4 {
+        let body = match_block(269, (extra, 2), 0, (0, 0));
+        let (output, expected) = decode(&body, 19 + extra as u16, 1);
+        assert_eq!(output.len(), PREFIX.len() + 19 + extra as usize);
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn distance_extra_bits() {
+    // Distance symbol 4: base 5, 1 extra bit. Length symbol 257: 3, no extra bits.
+    for extra in 0..2 {
+        let body = match_block(257, (0, 0), 4, (extra, 1));
+        let (output, expected) = decode(&body, 3, 5 + extra as u16);
+        assert_eq!(output, expected);
+    }
+    // Symbol 269 with distance symbol 5: base 7, 1 extra bit, i.e. nonzero
+    // extra bits on both sides of the match.
+    for extra in 0..2 {
+        let body = match_block(269, (0b10, 2), 5, (extra, 1));
+        let (output, expected) = decode(&body, 21, 7 + extra as u16);
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn distance_extra_bits_across_bytes() {
+    // Distance symbol 29: base 24577, 13 extra bits. Together with its 5-bit
+    // code and the 7-bit length code they span three bytes, at every bit
+    // offset the leading 9-bit literals give them.
+    let history: Vec<u8> = (0..1u32 << 15).map(|i| (i * 7 % 251) as u8).collect();
+    for shift in 0..8 {
+        for extra in [0, 0x1555, 0x1fff] {
+            let mut writer = BitWriter::default();
+            // A non-final stored block of the history.
+            writer.write_bits(0, 3);
+            writer.write_bits(0, 5);
+            for half in [history.len() as u32, !history.len() as u32] {
+                writer.write_bits(half & 0xffff, 16);
+            }
+            for &byte in &history {
+                writer.write_bits(byte as u32, 8);
+            }
+            writer.write_bits(1, 1);
+            writer.write_bits(1, 2);
+            for _ in 0..shift {
+                writer.write_code(0x190 + (200 - 144), 9);
+            }
+            writer.write_code(1, 7);
+            writer.write_code(29, 5);
+            writer.write_bits(extra, 13);
+            writer.write_code(0, 7);
+
+            let mut expected = history.clone();
+            expected.extend(std::iter::repeat_n(200, shift));
+            let start = expected.len() - (24577 + extra as usize);
+            expected.extend_from_within(start..start + 3);
+
+            let data = gzip_member(&writer.finish(), &expected);
+            assert_eq!(
+                ripgzip::decompress_to_vec(data.as_slice()).unwrap(),
+                expected,
+                "shift {}, extra {:#x}",
+                shift,
+                extra
+            );
+        }
+    }
+}
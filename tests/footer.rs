@@ -0,0 +1,109 @@
+mod common;
+
+use common::gzip_member;
+use ripgzip::GzDecoder;
+
+#[test]
+fn make_footer_matches_gzip() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut output = vec![];
+    ripgzip::decompress(&data[..], &mut output).unwrap();
+
+    let footer = ripgzip::make_footer(&output);
+    let mut bytes = vec![];
+    footer.write_to(&mut bytes).unwrap();
+    assert_eq!(bytes, &data[data.len() - 8..]);
+}
+
+#[test]
+fn whole_stream_crc() {
+    let member = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let data = [&member[..], &member[..]].concat();
+
+    let mut output = vec![];
+    let crc = ripgzip::decompress_whole_crc(data.as_slice(), &mut output).unwrap();
+    assert_eq!(
+        output.len(),
+        2 * ripgzip::decompress_to_vec(&member[..]).unwrap().len()
+    );
+    assert_eq!(crc, ripgzip::make_footer(&output).data_crc32);
+    assert_ne!(
+        crc,
+        u32::from_le_bytes(member[member.len() - 8..][..4].try_into().unwrap())
+    );
+}
+
+#[test]
+fn decompressed_byte_count() {
+    let member = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let data = [&member[..], &member[..], &member[..]].concat();
+
+    let mut output = vec![];
+    let count = ripgzip::decompress_counted(data.as_slice(), &mut output).unwrap();
+    assert_eq!(count, output.len() as u64);
+    assert_eq!(
+        count,
+        3 * ripgzip::decompress_to_vec(&member[..]).unwrap().len() as u64
+    );
+}
+
+#[test]
+fn header_callback() {
+    let data = include_bytes!("../data/ok/09-concat.gz");
+    let mut headers = vec![];
+    let mut output = vec![];
+    ripgzip::decompress_with(&data[..], &mut output, |header| {
+        headers.push((header.name.clone(), header.modification_time))
+    })
+    .unwrap();
+    assert_eq!(output, ripgzip::decompress_to_vec(&data[..]).unwrap());
+
+    let expected: Vec<_> = ripgzip::GzipReader::new(&data[..])
+        .members()
+        .map(|member| {
+            let (header, _data) = member.unwrap();
+            (header.name, header.modification_time)
+        })
+        .collect();
+    assert!(expected.len() > 1);
+    assert_eq!(headers, expected);
+
+    // Called before the body is decoded, so even for a corrupt body.
+    let mut data = data[..20].to_vec();
+    data.extend_from_slice(&[0xff; 8]);
+    let mut calls = 0;
+    assert!(ripgzip::decompress_with(data.as_slice(), std::io::sink(), |_| calls += 1).is_err());
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn crc_covers_trailing_match() {
+    // A fixed block: literals "abc", then a match of length 258 at distance 3.
+    let body = [0x4b, 0x4c, 0x4a, 0x1e, 0x45, 0x00];
+    let expected = b"abc".repeat(87);
+
+    let data = gzip_member(&body, &expected);
+    assert_eq!(
+        ripgzip::decompress_to_vec(data.as_slice()).unwrap(),
+        expected
+    );
+    let mut decoder = GzDecoder::new(data.as_slice());
+    let mut buf = [0u8; 7];
+    let mut output = vec![];
+    loop {
+        match decoder.decode_into_buffer(&mut buf).unwrap() {
+            0 => break,
+            size => output.extend_from_slice(&buf[..size]),
+        }
+    }
+    assert_eq!(output, expected);
+
+    // A footer whose CRC leaves out the expanded match must be rejected.
+    let mut data = gzip_member(&body, b"abc");
+    let size_pos = data.len() - 4;
+    data[size_pos..].copy_from_slice(&(expected.len() as u32).to_le_bytes());
+    let err = ripgzip::decompress(data.as_slice(), std::io::sink()).unwrap_err();
+    assert!(err.to_string().contains("crc32 check failed"), "{:#}", err);
+    let mut decoder = GzDecoder::new(data.as_slice());
+    assert!(decoder.decode_into_buffer(&mut [0u8; 1024]).is_err());
+}
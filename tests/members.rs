@@ -0,0 +1,151 @@
+use ripgzip::GzipReader;
+
+const CARGO: &[u8] = include_bytes!("../data/ok/00-Cargo.toml.gz");
+const PAGE: &[u8] = include_bytes!("../data/ok/01-page.gz");
+
+/// `member`, which has no optional header fields, with FNAME set to `name`.
+fn named_member(name: &str, member: &[u8]) -> Vec<u8> {
+    [
+        &member[..3],
+        &[1 << 3],
+        &member[4..10],
+        name.as_bytes(),
+        b"\0",
+        &member[10..],
+    ]
+    .concat()
+}
+
+#[test]
+fn members() {
+    let data = [named_member("a.txt", CARGO), named_member("b.txt", PAGE)].concat();
+    let members: Vec<_> = GzipReader::new(data.as_slice())
+        .members()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(members.len(), 2);
+    assert_eq!(members[0].0.name.as_deref(), Some("a.txt"));
+    assert_eq!(members[0].1, ripgzip::decompress_to_vec(CARGO).unwrap());
+    assert_eq!(members[1].0.name.as_deref(), Some("b.txt"));
+    assert_eq!(members[1].1, ripgzip::decompress_to_vec(PAGE).unwrap());
+
+    let data = include_bytes!("../data/ok/09-concat.gz");
+    let joined: Vec<u8> = GzipReader::new(&data[..])
+        .members()
+        .map(|member| member.unwrap().1)
+        .collect::<Vec<_>>()
+        .concat();
+    assert_eq!(joined, ripgzip::decompress_to_vec(&data[..]).unwrap());
+}
+
+#[test]
+fn members_reset_window() {
+    // A fixed block starting with a match at distance 1, reaching into the
+    // previous member's output.
+    let data = [CARGO, &CARGO[..10], &[0x03, 0x02, 0x00], &[0; 8]].concat();
+
+    let mut members = GzipReader::new(data.as_slice()).members();
+    assert_eq!(
+        members.next().unwrap().unwrap().1,
+        ripgzip::decompress_to_vec(CARGO).unwrap()
+    );
+    let err = members.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("bad dist"), "{:#}", err);
+    assert!(members.next().is_none());
+}
+
+#[test]
+fn members_check_footer() {
+    let bad = include_bytes!("../data/corrupted/01-bad-crc32.gz");
+    let data = [CARGO, &bad[..], CARGO].concat();
+
+    let mut members = GzipReader::new(data.as_slice()).members();
+    assert_eq!(
+        members.next().unwrap().unwrap().1,
+        ripgzip::decompress_to_vec(CARGO).unwrap()
+    );
+    let err = members.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("crc32 check failed"), "{:#}", err);
+    assert!(members.next().is_none());
+}
+
+#[test]
+fn decode_member_in_custom_frames() {
+    // Each member prefixed with its length and followed by frame padding.
+    let mut data = vec![];
+    for (name, member) in [("a.txt", CARGO), ("b.txt", PAGE)] {
+        let member = named_member(name, member);
+        data.extend_from_slice(&(member.len() as u32).to_le_bytes());
+        data.extend_from_slice(&member);
+        data.extend_from_slice(b"PAD");
+    }
+
+    let mut reader = data.as_slice();
+    for (name, member) in [("a.txt", CARGO), ("b.txt", PAGE)] {
+        let payload = ripgzip::decompress_to_vec(member).unwrap();
+        let len = u32::from_le_bytes(reader[..4].try_into().unwrap()) as usize;
+        reader = &reader[4..];
+        let rest = reader.len();
+        let (header, output, footer) = ripgzip::decode_member(&mut reader).unwrap();
+        assert_eq!(rest - reader.len(), len);
+        assert_eq!(header.name.as_deref(), Some(name));
+        assert_eq!(output, payload);
+        assert_eq!(footer, ripgzip::make_footer(&payload));
+        assert_eq!(&reader[..3], b"PAD");
+        reader = &reader[3..];
+    }
+    assert!(reader.is_empty());
+    assert!(ripgzip::decode_member(&mut reader).is_err());
+}
+
+#[test]
+fn ra_and_qs_extra_subfields() {
+    // dictzip's RA: version 1, chunk length 0xe5a4, one chunk of 0x1234
+    // compressed bytes; then a QS subfield.
+    let ra = [1, 0, 0xa4, 0xe5, 1, 0, 0x34, 0x12];
+    let qs = b"quality";
+    let mut extra = vec![b'R', b'A', ra.len() as u8, 0];
+    extra.extend_from_slice(&ra);
+    extra.extend_from_slice(&[b'Q', b'S', qs.len() as u8, 0]);
+    extra.extend_from_slice(qs);
+
+    let data = [
+        &CARGO[..3],
+        &[1 << 2],
+        &CARGO[4..10],
+        &(extra.len() as u16).to_le_bytes(),
+        &extra,
+        &CARGO[10..],
+    ]
+    .concat();
+
+    let (header, _flags) = GzipReader::new(data.as_slice())
+        .read_header()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        header.extra_subfields().unwrap(),
+        [(*b"RA", &ra[..]), (*b"QS", &qs[..])]
+    );
+}
+
+#[test]
+fn is_multistream() {
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    let single = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let concat = include_bytes!("../data/ok/09-concat.gz");
+    assert!(!ripgzip::is_multistream(Cursor::new(&single[..])).unwrap());
+    assert!(ripgzip::is_multistream(Cursor::new(&concat[..])).unwrap());
+
+    let data = [&b"junk"[..], single, single].concat();
+    let mut input = Cursor::new(data.as_slice());
+    input.seek(SeekFrom::Start(4)).unwrap();
+    assert!(ripgzip::is_multistream(&mut input).unwrap());
+    assert_eq!(input.position(), 4);
+
+    // Trailing garbage isn't another member.
+    let data = [&single[..], b"\x1f\x00"].concat();
+    assert!(!ripgzip::is_multistream(Cursor::new(data.as_slice())).unwrap());
+    assert!(ripgzip::is_multistream(Cursor::new(&b"not gzip"[..])).is_err());
+}
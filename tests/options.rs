@@ -0,0 +1,256 @@
+use ripgzip::{DecodeOptions, GzDecoder};
+
+#[test]
+fn require_header_crc() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    assert!(ripgzip::decompress(&data[..], std::io::sink()).is_ok());
+
+    let options = DecodeOptions {
+        require_header_crc: true,
+        ..DecodeOptions::default()
+    };
+    assert!(ripgzip::decompress_with_options(&data[..], std::io::sink(), &options).is_err());
+
+    let data = include_bytes!("../data/ok/10-header-crc16.gz");
+    assert!(ripgzip::decompress_with_options(&data[..], std::io::sink(), &options).is_ok());
+}
+
+struct EndlessStoredBlocks {
+    header: &'static [u8],
+    pos: usize,
+}
+
+impl std::io::Read for EndlessStoredBlocks {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        const BLOCK: [u8; 5] = [0x00, 0x00, 0x00, 0xff, 0xff];
+        for byte in buf.iter_mut() {
+            *byte = match self.header.get(self.pos) {
+                Some(&byte) => byte,
+                None => BLOCK[(self.pos - self.header.len()) % BLOCK.len()],
+            };
+            self.pos += 1;
+        }
+        Ok(buf.len())
+    }
+}
+
+#[test]
+fn max_compressed_bytes() {
+    let input = std::io::BufReader::new(EndlessStoredBlocks {
+        header: &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255],
+        pos: 0,
+    });
+    let options = DecodeOptions {
+        max_compressed_bytes: Some(1000),
+        ..DecodeOptions::default()
+    };
+    let err = ripgzip::decompress_with_options(input, std::io::sink(), &options).unwrap_err();
+    assert!(err.to_string().contains("compressed size limit exceeded"));
+}
+
+#[test]
+fn max_blocks_per_member() {
+    let options = DecodeOptions {
+        max_blocks_per_member: Some(100),
+        ..DecodeOptions::default()
+    };
+    let input = std::io::BufReader::new(EndlessStoredBlocks {
+        header: &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255],
+        pos: 0,
+    });
+    let err = ripgzip::decompress_with_options(input, std::io::sink(), &options).unwrap_err();
+    assert!(err.to_string().contains("block count limit exceeded"));
+
+    let input = std::io::BufReader::new(EndlessStoredBlocks {
+        header: &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255],
+        pos: 0,
+    });
+    let mut decoder = GzDecoder::with_options(input, options.clone());
+    let err = decoder.decode_into_buffer(&mut [0u8; 16]).unwrap_err();
+    assert!(err.to_string().contains("block count limit exceeded"));
+    assert_eq!(decoder.last_stats().blocks, 101);
+
+    // The limit is per member: two members of 100 blocks each are fine.
+    let mut member = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    for _ in 0..99 {
+        member.extend_from_slice(&[0x00, 0x00, 0x00, 0xff, 0xff]);
+    }
+    member.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    member.extend_from_slice(&[0; 8]);
+    let data = member.repeat(2);
+    ripgzip::decompress_with_options(data.as_slice(), std::io::sink(), &options).unwrap();
+    let mut decoder = GzDecoder::with_options(data.as_slice(), options);
+    assert_eq!(decoder.decode_into_buffer(&mut [0u8; 16]).unwrap(), 0);
+}
+
+#[test]
+fn verify_stored_nlen() {
+    // Starts with a stored block, so NLEN is right after the 10-byte header,
+    // the 3-bit block header padded to a byte and LEN.
+    let good = include_bytes!("../data/ok/07-war-and-peace.txt.gz.gz");
+    let mut data = good.to_vec();
+    data[10 + 1 + 2] ^= 1;
+
+    let err = ripgzip::decompress(data.as_slice(), std::io::sink()).unwrap_err();
+    assert!(err.to_string().contains("nlen check failed"), "{:#}", err);
+
+    let options = DecodeOptions {
+        verify_stored_nlen: false,
+        ..DecodeOptions::default()
+    };
+    let expected = ripgzip::decompress_to_vec(&good[..]).unwrap();
+    let mut output = vec![];
+    ripgzip::decompress_with_options(data.as_slice(), &mut output, &options).unwrap();
+    assert_eq!(output, expected);
+
+    let mut decoder = GzDecoder::with_options(data.as_slice(), options);
+    let mut buf = [0u8; 16];
+    assert_eq!(decoder.decode_into_buffer(&mut buf).unwrap(), 16);
+    assert_eq!(buf, expected[..16]);
+}
+
+#[test]
+fn fast_skips_checksums() {
+    let data = include_bytes!("../data/corrupted/01-bad-crc32.gz");
+    assert!(ripgzip::decompress(&data[..], std::io::sink()).is_err());
+    let options = DecodeOptions::fast();
+    assert!(ripgzip::decompress_with_options(&data[..], std::io::sink(), &options).is_ok());
+}
+
+#[test]
+fn output_buffer_size() {
+    let data = include_bytes!("../data/ok/01-page.gz");
+    let mut expected = vec![];
+    ripgzip::decompress(&data[..], &mut expected).unwrap();
+
+    for size in [1, 64 << 10] {
+        let options = DecodeOptions {
+            output_buffer_size: size,
+            ..DecodeOptions::default()
+        };
+        let mut output = vec![];
+        ripgzip::decompress_with_options(&data[..], &mut output, &options).unwrap();
+        assert_eq!(output, expected);
+    }
+
+    let options = DecodeOptions {
+        output_buffer_size: 0,
+        ..DecodeOptions::default()
+    };
+    assert!(ripgzip::decompress_with_options(&data[..], std::io::sink(), &options).is_err());
+}
+
+#[test]
+fn stop_after_last_member() {
+    let member = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut data = member.to_vec();
+    data.extend_from_slice(b"trailing metadata");
+    assert!(ripgzip::decompress(data.as_slice(), std::io::sink()).is_err());
+
+    let options = DecodeOptions {
+        stop_after_last_member: true,
+        ..DecodeOptions::default()
+    };
+    let mut input = data.as_slice();
+    let mut output = vec![];
+    let consumed = ripgzip::decompress_counting(&mut input, &mut output, &options).unwrap();
+    assert_eq!(consumed, member.len() as u64);
+    assert_eq!(input, b"trailing metadata");
+    assert_eq!(output, ripgzip::decompress_to_vec(&member[..]).unwrap());
+
+    // Read one byte at a time, the magic's second byte only shows up after a
+    // refill.
+    let expected = ripgzip::decompress_to_vec(&member[..]).unwrap();
+    let mut data = [&member[..], &member[..]].concat();
+    data.extend_from_slice(b"\x1fmetadata");
+    let mut output = vec![];
+    let input = std::io::BufReader::with_capacity(1, data.as_slice());
+    ripgzip::decompress_with_options(input, &mut output, &options).unwrap();
+    assert_eq!(output, expected.repeat(2));
+}
+
+#[test]
+fn buffer_until_verified() {
+    let mut data = include_bytes!("../data/ok/00-Cargo.toml.gz").to_vec();
+    let expected = ripgzip::decompress_to_vec(data.as_slice()).unwrap();
+    let options = DecodeOptions {
+        buffer_until_verified: Some(expected.len()),
+        ..DecodeOptions::default()
+    };
+    let mut output = vec![];
+    ripgzip::decompress_with_options(data.as_slice(), &mut output, &options).unwrap();
+    assert_eq!(output, expected);
+
+    let crc_pos = data.len() - 8;
+    data[crc_pos] ^= 1;
+    let mut output = vec![];
+    assert!(ripgzip::decompress(data.as_slice(), &mut output).is_err());
+    assert!(!output.is_empty());
+
+    let mut output = vec![];
+    let err = ripgzip::decompress_with_options(data.as_slice(), &mut output, &options).unwrap_err();
+    assert!(err.to_string().contains("crc32 check failed"), "{:#}", err);
+    assert!(output.is_empty());
+
+    let options = DecodeOptions {
+        buffer_until_verified: Some(expected.len() - 1),
+        ..DecodeOptions::default()
+    };
+    let mut output = vec![];
+    let err = ripgzip::decompress_with_options(data.as_slice(), &mut output, &options).unwrap_err();
+    assert!(format!("{:#}", err).contains("limit exceeded"), "{:#}", err);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn max_window_distance() {
+    let data = include_bytes!("../data/ok/06-war-and-peace.txt.gz");
+    let expected = ripgzip::decompress_to_vec(data.as_slice()).unwrap();
+
+    let options = DecodeOptions {
+        max_window_distance: Some(1024),
+        ..DecodeOptions::default()
+    };
+    let err =
+        ripgzip::decompress_with_options(data.as_slice(), std::io::sink(), &options).unwrap_err();
+    assert!(format!("{:#}", err).contains("bad dist"), "{:#}", err);
+
+    let mut decoder = GzDecoder::with_options(data.as_slice(), options);
+    let mut buf = vec![0u8; expected.len()];
+    assert!(
+        std::iter::repeat_with(|| decoder.decode_into_buffer(&mut buf))
+            .find(|result| !matches!(result, Ok(size) if *size > 0))
+            .unwrap()
+            .is_err()
+    );
+
+    let options = DecodeOptions {
+        max_window_distance: Some(32 << 10),
+        ..DecodeOptions::default()
+    };
+    let mut output = vec![];
+    ripgzip::decompress_with_options(data.as_slice(), &mut output, &options).unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn huffman_table_budget() {
+    let options = DecodeOptions {
+        huffman_table_budget: Some(16),
+        ..DecodeOptions::default()
+    };
+    for data in [
+        &include_bytes!("../data/ok/01-page.gz")[..],
+        &include_bytes!("../data/ok/06-war-and-peace.txt.gz")[..],
+    ] {
+        let expected = ripgzip::decompress_to_vec(data).unwrap();
+        let mut output = vec![];
+        ripgzip::decompress_with_options(data, &mut output, &options).unwrap();
+        assert_eq!(output, expected);
+
+        let mut output = vec![];
+        let mut decoder = GzDecoder::with_options(data, options.clone());
+        std::io::copy(&mut decoder, &mut output).unwrap();
+        assert_eq!(output, expected);
+    }
+}
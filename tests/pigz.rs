@@ -0,0 +1,71 @@
+mod common;
+
+use common::decompress;
+
+/// A fixed block with the literals "pigz splits input into blocks. ".
+const FIRST_CHUNK: [u8; 31] = [
+    0x2a, 0xc8, 0x4c, 0xaf, 0x52, 0x28, 0x2e, 0xc8, 0xc9, 0x2c, 0x29, 0x56, 0xc8, 0xcc, 0x2b, 0x28,
+    0x2d, 0x51, 0xc8, 0xcc, 0x2b, 0xc9, 0x57, 0x48, 0xca, 0xc9, 0x4f, 0xce, 0x2e, 0xd6, 0x53,
+];
+
+/// A sync flush: the rest of the byte, then an empty non-final stored block,
+/// like `pigz` writes between its blocks.
+const SYNC_FLUSH: [u8; 5] = [0x00, 0x00, 0x00, 0xff, 0xff];
+
+/// Fixed blocks shaped like pigz output: every chunk but the first starts
+/// with matches reaching back into the previous one. Chunk `i` is a match of
+/// length 31 at distance 31, the literal `'0' + i`, a match of length 10 at
+/// distance 42, and a newline.
+fn chunk(i: u8) -> [u8; 7] {
+    [
+        0x22,
+        0x20,
+        0x6d,
+        [0x80, 0x88, 0x84, 0x8c][i as usize],
+        0x50,
+        0xc9,
+        0x05,
+    ]
+}
+
+/// One member of the first `count` chunks, each ended by a sync flush.
+fn flushed_member(count: u8) -> (Vec<u8>, Vec<u8>) {
+    let mut body = [&FIRST_CHUNK[..], &[0x00], &SYNC_FLUSH].concat();
+    let mut expected = b"pigz splits input into blocks. ".to_vec();
+    for i in 0..count - 1 {
+        body.extend_from_slice(&chunk(i));
+        body.extend_from_slice(&SYNC_FLUSH);
+        for (len, dist) in [(31, 31), (0, 0), (10, 42)] {
+            for _ in 0..len {
+                expected.push(expected[expected.len() - dist]);
+            }
+            if len == 0 {
+                expected.push(b'0' + i);
+            }
+        }
+        expected.push(b'\n');
+    }
+    // An empty final fixed block.
+    body.extend_from_slice(&[0x03, 0x00]);
+
+    let mut member = [&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255][..], &body].concat();
+    ripgzip::make_footer(&expected)
+        .write_to(&mut member)
+        .unwrap();
+    (member, expected)
+}
+
+#[test]
+fn single_member_with_flush_blocks() {
+    let (data, expected) = flushed_member(5);
+    assert_eq!(decompress(&data).unwrap(), expected);
+}
+
+#[test]
+fn multiple_flushed_members() {
+    let (mut data, first) = flushed_member(5);
+    let (second_member, second) = flushed_member(2);
+    data.extend(second_member);
+
+    assert_eq!(decompress(&data).unwrap(), [first, second].concat());
+}
@@ -0,0 +1,14 @@
+use std::io::Read;
+
+#[test]
+fn decompress_read_over_chain() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let expected = ripgzip::decompress_to_vec(&data[..]).unwrap();
+
+    for split in 0..=data.len() {
+        let (head, tail) = data.split_at(split);
+        let mut output = vec![];
+        ripgzip::decompress_read(head.chain(tail), &mut output).unwrap();
+        assert_eq!(output, expected, "split at {}", split);
+    }
+}
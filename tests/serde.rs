@@ -0,0 +1,35 @@
+#![cfg(feature = "serde")]
+
+use ripgzip::{CompressionMethod, MemberFooter, MemberHeader};
+
+#[test]
+fn header_json_round_trip() {
+    let header = MemberHeader {
+        compression_method: CompressionMethod::Deflate,
+        modification_time: 0x12345678,
+        extra: Some(vec![b'A', b'p', 1, 0, 0xff]),
+        name: Some("file.txt".to_string()),
+        comment: None,
+        extra_flags: 2,
+        os: 3,
+        has_crc: true,
+        is_text: false,
+    };
+    let json = serde_json::to_string(&header).unwrap();
+    assert!(json.contains(r#""extra":[65,112,1,0,255]"#), "{}", json);
+    assert_eq!(serde_json::from_str::<MemberHeader>(&json).unwrap(), header);
+
+    let unknown = CompressionMethod::Unknown(9);
+    let json = serde_json::to_string(&unknown).unwrap();
+    assert_eq!(
+        serde_json::from_str::<CompressionMethod>(&json).unwrap(),
+        unknown
+    );
+}
+
+#[test]
+fn footer_json_round_trip() {
+    let footer = ripgzip::make_footer(b"hello");
+    let json = serde_json::to_string(&footer).unwrap();
+    assert_eq!(serde_json::from_str::<MemberFooter>(&json).unwrap(), footer);
+}
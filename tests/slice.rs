@@ -0,0 +1,34 @@
+#[test]
+fn decompress_into_slice() {
+    let data = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let expected = ripgzip::decompress_to_vec(&data[..]).unwrap();
+
+    let mut out = vec![0; expected.len()];
+    let size = ripgzip::decompress_into_slice(&data[..], &mut out).unwrap();
+    assert_eq!(size, expected.len());
+    assert_eq!(out, expected);
+
+    let mut out = vec![0; expected.len() + 10];
+    let size = ripgzip::decompress_into_slice(&data[..], &mut out).unwrap();
+    assert_eq!(out[..size], expected);
+
+    for len in [0, 6, expected.len() - 1] {
+        let mut out = vec![0; len];
+        let err = ripgzip::decompress_into_slice(&data[..], &mut out).unwrap_err();
+        assert!(err.to_string().contains("doesn't fit"), "{:#}", err);
+        assert_eq!(out, expected[..len]);
+    }
+
+    // A corrupt member that exactly fills the slice still reports the
+    // corruption.
+    let mut corrupt = data.to_vec();
+    let crc_pos = corrupt.len() - 8;
+    corrupt[crc_pos] ^= 1;
+    let mut out = vec![0; expected.len()];
+    let err = ripgzip::decompress_into_slice(corrupt.as_slice(), &mut out).unwrap_err();
+    assert!(
+        matches!(err, ripgzip::DecodeError::Crc32Mismatch { .. }),
+        "{:#}",
+        err
+    );
+}
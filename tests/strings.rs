@@ -0,0 +1,86 @@
+/// A gzip member holding `text` in a single stored block.
+fn stored_gzip(text: &[u8]) -> Vec<u8> {
+    let len = text.len() as u16;
+    let mut member = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255, 1];
+    member.extend_from_slice(&len.to_le_bytes());
+    member.extend_from_slice(&(!len).to_le_bytes());
+    member.extend_from_slice(text);
+    ripgzip::make_footer(text).write_to(&mut member).unwrap();
+    member
+}
+
+#[test]
+fn decompress_to_string() {
+    let data = stored_gzip("привет, world\n".as_bytes());
+    assert_eq!(
+        ripgzip::decompress_to_string(data.as_slice()).unwrap(),
+        "привет, world\n"
+    );
+}
+
+#[test]
+fn decompress_to_string_lossy() {
+    let data = stored_gzip(b"ab\xffcd");
+    assert!(ripgzip::decompress_to_string(data.as_slice()).is_err());
+    assert_eq!(
+        ripgzip::decompress_to_string_lossy(data.as_slice()).unwrap(),
+        "ab\u{fffd}cd"
+    );
+}
+
+#[test]
+fn decompress_to_string_with_options() {
+    let options = ripgzip::TextOptions {
+        strip_bom: true,
+        normalize_newlines: true,
+    };
+
+    let data = stored_gzip(b"first\r\nsecond\r\n");
+    assert_eq!(
+        ripgzip::decompress_to_string_with_options(data.as_slice(), &options).unwrap(),
+        "first\nsecond\n"
+    );
+
+    let data = stored_gzip(b"\xef\xbb\xbfhello");
+    assert_eq!(
+        ripgzip::decompress_to_string_with_options(data.as_slice(), &options).unwrap(),
+        "hello"
+    );
+}
+
+#[test]
+fn decompress_lines() {
+    let log = "2024-01-01 start\n2024-01-01 пишем\r\n\n2024-01-02 stop";
+    let data = stored_gzip(log.as_bytes());
+    let lines: Vec<_> = ripgzip::decompress_lines(data.as_slice())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        lines,
+        [
+            "2024-01-01 start",
+            "2024-01-01 пишем\r",
+            "",
+            "2024-01-02 stop"
+        ]
+    );
+
+    let data = stored_gzip(b"one\ntwo\n");
+    let lines: Vec<_> = ripgzip::decompress_lines(data.as_slice())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(lines, ["one", "two"]);
+
+    let data = stored_gzip(b"ok\nbad \xff\nok again\n");
+    let lines: Vec<_> = ripgzip::decompress_lines(data.as_slice()).collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].is_err());
+    assert_eq!(lines[2].as_ref().unwrap(), "ok again");
+
+    let mut data = stored_gzip(b"line\n");
+    let crc_pos = data.len() - 8;
+    data[crc_pos] ^= 1;
+    let mut lines = ripgzip::decompress_lines(data.as_slice());
+    assert!(lines.next().unwrap().is_err());
+    assert!(lines.next().is_none());
+}
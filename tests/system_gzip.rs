@@ -0,0 +1,69 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn system_gzip(data: &[u8], level: u32) -> Option<Vec<u8>> {
+    let mut child = Command::new("gzip")
+        .arg("-c")
+        .arg(format!("-{}", level))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let input = data.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().ok()?;
+    writer.join().unwrap().ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut state = 0x2545f4914f6cdd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+fn inputs() -> Vec<(&'static str, Vec<u8>)> {
+    let text = "The quick brown fox jumps over the lazy dog.\n".repeat(2000);
+    vec![
+        ("empty", vec![]),
+        ("one byte", vec![b'x']),
+        ("random", random_bytes(100_000)),
+        ("repetitive", b"abcabcabd".repeat(120_000)),
+        ("zeros", vec![0; 1 << 20]),
+        ("text", text.into_bytes()),
+    ]
+}
+
+#[test]
+#[ignore = "needs a gzip binary"]
+fn round_trip_system_gzip() {
+    for (name, data) in inputs() {
+        for level in [1, 6, 9] {
+            let compressed = match system_gzip(&data, level) {
+                Some(compressed) => compressed,
+                None => {
+                    eprintln!("gzip is not available, skipping");
+                    return;
+                }
+            };
+            let mut output = vec![];
+            ripgzip::decompress(compressed.as_slice(), &mut output)
+                .unwrap_or_else(|err| panic!("{} at level {}: {:#}", name, level, err));
+            assert!(
+                output == data,
+                "{} at level {}: output differs",
+                name,
+                level
+            );
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use ripgzip::{ChecksumErrorPolicy, DecodeOptions, GzDecoder, Warning};
+
+// 2100-01-01T00:00:00Z
+const YEAR_2100: u32 = 4_102_444_800;
+
+#[test]
+fn future_modification_time() {
+    let plain = include_bytes!("../data/ok/00-Cargo.toml.gz");
+    let mut data = plain.to_vec();
+    data[4..8].copy_from_slice(&YEAR_2100.to_le_bytes());
+    let expected =
+        Warning::FutureModificationTime(UNIX_EPOCH + Duration::from_secs(YEAR_2100 as u64));
+
+    let mut output = vec![];
+    let warnings =
+        ripgzip::decompress_with_warnings(data.as_slice(), &mut output, &DecodeOptions::default())
+            .unwrap();
+    assert_eq!(output, ripgzip::decompress_to_vec(&plain[..]).unwrap());
+    assert_eq!(warnings, std::slice::from_ref(&expected));
+
+    let mut decoder = GzDecoder::new(data.as_slice());
+    let mut buf = [0u8; 16];
+    while decoder.decode_into_buffer(&mut buf).unwrap() > 0 {}
+    assert_eq!(decoder.warnings(), [expected]);
+
+    let warnings =
+        ripgzip::decompress_with_warnings(&plain[..], std::io::sink(), &DecodeOptions::default())
+            .unwrap();
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn checksum_mismatch() {
+    let bad = include_bytes!("../data/corrupted/01-bad-crc32.gz");
+    let good = include_bytes!("../data/ok/01-page.gz");
+    let first =
+        ripgzip::decompress_to_vec(&include_bytes!("../data/ok/00-Cargo.toml.gz")[..]).unwrap();
+    let second = ripgzip::decompress_to_vec(&good[..]).unwrap();
+    let data = [&bad[..], &good[..]].concat();
+
+    let options = DecodeOptions {
+        on_checksum_error: ChecksumErrorPolicy::KeepAndWarn,
+        ..DecodeOptions::default()
+    };
+    let mut output = vec![];
+    let warnings =
+        ripgzip::decompress_with_warnings(data.as_slice(), &mut output, &options).unwrap();
+    assert_eq!(output, [first, second.clone()].concat());
+    assert_eq!(warnings, [Warning::ChecksumMismatch(0)]);
+
+    let options = DecodeOptions {
+        on_checksum_error: ChecksumErrorPolicy::Skip,
+        buffer_until_verified: Some(1 << 20),
+        ..DecodeOptions::default()
+    };
+    let mut output = vec![];
+    let warnings =
+        ripgzip::decompress_with_warnings(data.as_slice(), &mut output, &options).unwrap();
+    assert_eq!(output, second);
+    assert!(warnings.is_empty());
+
+    let options = DecodeOptions {
+        on_checksum_error: ChecksumErrorPolicy::Skip,
+        ..DecodeOptions::default()
+    };
+    assert!(ripgzip::decompress_with_warnings(data.as_slice(), std::io::sink(), &options).is_err());
+    assert!(ripgzip::decompress(data.as_slice(), std::io::sink()).is_err());
+}
@@ -0,0 +1,48 @@
+const MEMBER: &[u8] = include_bytes!("../data/ok/00-Cargo.toml.gz");
+
+/// The member's DEFLATE body wrapped into a zlib stream.
+fn zlib_stream(header: [u8; 2]) -> Vec<u8> {
+    let mut adler = ripgzip::Adler32::new();
+    adler.update(&ripgzip::decompress_to_vec(MEMBER).unwrap());
+
+    let mut data = header.to_vec();
+    data.extend_from_slice(&MEMBER[10..MEMBER.len() - 8]);
+    data.extend_from_slice(&adler.finalize().to_be_bytes());
+    data
+}
+
+#[test]
+fn decompress_zlib() {
+    let expected = ripgzip::decompress_to_vec(MEMBER).unwrap();
+    for header in [[0x78, 0x01], [0x78, 0x9c], [0x78, 0xda], [0x08, 0x1d]] {
+        let data = zlib_stream(header);
+        let mut output = vec![];
+        ripgzip::decompress_zlib(data.as_slice(), &mut output).unwrap();
+        assert_eq!(output, expected);
+
+        let mut output = vec![];
+        ripgzip::decompress_auto(data.as_slice(), &mut output).unwrap();
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn zlib_errors() {
+    let check = |data: &[u8], msg: &str| {
+        let err = ripgzip::decompress_zlib(data, std::io::sink()).unwrap_err();
+        assert!(err.to_string().contains(msg), "{:#}", err);
+    };
+
+    let mut data = zlib_stream([0x78, 0x9c]);
+    let len = data.len();
+    data[len - 1] ^= 1;
+    check(&data, "adler32 check failed");
+
+    check(&zlib_stream([0x78, 0x9d]), "header check failed");
+    check(&zlib_stream([0x79, 0x18]), "compression method");
+    check(&zlib_stream([0x88, 0x1c]), "window size");
+    check(&zlib_stream([0x78, 0xbb]), "preset dictionaries");
+
+    let data = zlib_stream([0x78, 0x9c]);
+    check(&data[..data.len() - 2], "");
+}